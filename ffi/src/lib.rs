@@ -0,0 +1,4 @@
+// Everything here comes from the qr crate, this crate only exists to link it into a C library or a wasm module
+pub use qr::ffi::*;
+#[cfg(target_arch = "wasm32")]
+pub use qr::wasm::*;
@@ -0,0 +1,205 @@
+/* C interface to the qr encoder, generated from src/ffi.rs by cbindgen. Don't edit it by hand. */
+
+#ifndef QR_H
+#define QR_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * What a call came to, with a value for every variant of the library's Error and a few for the ABI itself
+ */
+typedef enum {
+  QR_STATUS_OK = 0,
+  QR_STATUS_TOO_LONG,
+  QR_STATUS_TOO_LONG_FOR_VERSION,
+  QR_STATUS_LOGO_TOO_BIG,
+  QR_STATUS_NOT_FOUND,
+  QR_STATUS_UNREADABLE,
+  QR_STATUS_TOO_DAMAGED,
+  QR_STATUS_UNSUPPORTED_MODE,
+  QR_STATUS_MISSING_FIELD,
+  QR_STATUS_INVALID_FIELD,
+  QR_STATUS_PAYLOAD_TOO_LONG,
+  QR_STATUS_WRONG_EC_LEVEL,
+  /**
+   * A pointer that has to be given was null
+   */
+  QR_STATUS_NULL_POINTER,
+  /**
+   * The buffer can't hold everything, the length needed has been written out instead
+   */
+  QR_STATUS_BUFFER_TOO_SMALL,
+  /**
+   * The image couldn't be encoded
+   */
+  QR_STATUS_RENDER_FAILED,
+  /**
+   * The scale and quiet zone make the image too big to draw, which comes after the ABI's own values to keep theirs
+   */
+  QR_STATUS_IMAGE_TOO_LARGE,
+} QrStatus;
+
+typedef enum {
+  QR_EC_LEVEL_L = 0,
+  QR_EC_LEVEL_M,
+  QR_EC_LEVEL_Q,
+  QR_EC_LEVEL_H,
+} QrEcLevel;
+
+typedef enum {
+  /**
+   * Densest mode that holds all of the data
+   */
+  QR_MODE_AUTO = 0,
+  QR_MODE_NUMERIC,
+  QR_MODE_ALPHANUMERIC,
+  QR_MODE_BYTE,
+} QrMode;
+
+typedef enum {
+  QR_FORMAT_PNG = 0,
+  QR_FORMAT_SVG,
+  QR_FORMAT_JPEG,
+  QR_FORMAT_BMP,
+  QR_FORMAT_GIF,
+} QrFormat;
+
+/**
+ * A generated code, opaque to C
+ */
+typedef struct QrSymbol QrSymbol;
+
+/**
+ * How to make and draw a symbol, start from qr_options_default and change what's needed
+ */
+typedef struct {
+  /**
+   * One of QrEcLevel
+   */
+  uint32_t ec_level;
+  /**
+   * One of QrMode
+   */
+  uint32_t mode;
+  /**
+   * 1 to 40, or 0 for the smallest that fits
+   */
+  uint32_t version;
+  /**
+   * 0 to 7, or -1 for the one with the lowest penalty
+   */
+  int32_t mask;
+  /**
+   * Pixels per module in raster images, SVGs use it for their width and height
+   */
+  uint32_t scale;
+  /**
+   * Width of the light border in modules
+   */
+  uint32_t quiet_zone;
+  /**
+   * Colors as 0xRRGGBBAA
+   */
+  uint32_t dark;
+  uint32_t light;
+} QrOptions;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The same defaults as the command line: EC level Q, any mode, version and mask, 8 pixels per module and black on white
+ */
+QrOptions qr_options_default(void);
+
+/**
+ * Makes a symbol out of length bytes of data, which is written to out and has to be freed with qr_symbol_free
+ * options can be null for the defaults, and out is left alone if anything goes wrong
+ *
+ * # Safety
+ * data has to point to length readable bytes, options to a QrOptions if it isn't null, and out to a writable pointer
+ */
+QrStatus qr_symbol_new(const uint8_t *data,
+                       size_t length,
+                       const QrOptions *options,
+                       QrSymbol **out);
+
+/**
+ * # Safety
+ * symbol has to have come from qr_symbol_new and not been freed yet, or be null
+ */
+void qr_symbol_free(QrSymbol *symbol);
+
+/**
+ * Modules along each side, not counting the quiet zone, or 0 for a null symbol
+ *
+ * # Safety
+ * symbol has to be a live symbol from qr_symbol_new, or null
+ */
+size_t qr_symbol_size(const QrSymbol *symbol);
+
+/**
+ * # Safety
+ * symbol has to be a live symbol from qr_symbol_new, or null
+ */
+uint32_t qr_symbol_version(const QrSymbol *symbol);
+
+/**
+ * # Safety
+ * symbol has to be a live symbol from qr_symbol_new, or null
+ */
+int32_t qr_symbol_mask(const QrSymbol *symbol);
+
+/**
+ * # Safety
+ * symbol has to be a live symbol from qr_symbol_new, or null
+ */
+QrEcLevel qr_symbol_ec_level(const QrSymbol *symbol);
+
+/**
+ * Whether the module at column x, row y is dark, which is never the case outside the symbol
+ *
+ * # Safety
+ * symbol has to be a live symbol from qr_symbol_new, or null
+ */
+bool qr_symbol_is_dark(const QrSymbol *symbol, size_t x, size_t y);
+
+/**
+ * Copies every module into buffer row by row, 1 for dark and 0 for light, which takes size * size bytes
+ *
+ * # Safety
+ * symbol has to be a live symbol from qr_symbol_new, and buffer has to point to length writable bytes
+ */
+QrStatus qr_symbol_modules(const QrSymbol *symbol,
+                           uint8_t *buffer,
+                           size_t length);
+
+/**
+ * Draws the symbol in a format and copies the file into buffer, writing how many bytes it takes to written
+ * Call it with a null buffer first to find out how big the buffer has to be
+ * format is one of QrFormat, anything else gives QR_STATUS_INVALID_FIELD
+ *
+ * # Safety
+ * symbol has to be a live symbol from qr_symbol_new, buffer has to point to capacity writable bytes unless it's
+ * null, and written has to point to a writable size_t
+ */
+QrStatus qr_symbol_render(const QrSymbol *symbol,
+                          uint32_t format,
+                          uint8_t *buffer,
+                          size_t capacity,
+                          size_t *written);
+
+/**
+ * What a status means in English, as a static string that mustn't be freed
+ * status is one of QrStatus, anything else gets a message saying so
+ */
+const char *qr_status_message(uint32_t status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* QR_H */
@@ -0,0 +1,229 @@
+use crate::qr::{EcLevel, Metadata};
+use crate::render::{self, Format, RenderOptions};
+use crate::segment::{Mode, Segment};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File};
+use std::collections::HashMap;
+use std::io::{self, BufWriter};
+use std::path::{Component, Path, PathBuf};
+
+// Makes lots of codes at once out of a CSV or JSON Lines file, one code per row
+// Every row names its data and output file, and can override the settings the rest of the batch shares
+
+// A row of the batch file, where anything left out comes from the batch's settings
+// CSV files have these as their header, and JSON Lines files as the keys of each object
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Row {
+    pub data: String,
+    // Where to save the code, relative to the output directory, with the extension picking the format
+    pub output: String,
+    pub ec: Option<String>,
+    // numeric, alphanumeric, byte or auto
+    pub mode: Option<String>,
+    pub version: Option<usize>,
+    pub mask: Option<usize>,
+    // Image size in pixels, or pixels per module like 10x
+    pub scale: Option<String>,
+    pub quiet_zone: Option<u32>,
+    pub dark: Option<String>,
+    pub light: Option<String>,
+}
+
+// What every row gets unless it says otherwise
+#[derive(Clone)]
+pub struct Settings {
+    pub ec_level: EcLevel,
+    // Mode to write the data in, or None for the densest one that fits it
+    pub mode: Option<Mode>,
+    pub version: Option<usize>,
+    pub mask: Option<usize>,
+    pub bump_ec: bool,
+    // Format to save every code in, instead of going by the extension
+    pub format: Option<Format>,
+    pub options: RenderOptions,
+}
+
+// How a row went
+#[derive(Clone, Debug, Serialize)]
+pub struct Outcome {
+    // Line of the file the row starts on
+    pub line: usize,
+    // Where the code was meant to go, if the row could be read at all
+    pub output: Option<String>,
+    // Why it didn't work, or None if the code was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // What the code ended up as, if it was made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
+// Reads the rows of a batch file, as JSON Lines if it ends in .jsonl or .ndjson and as CSV otherwise
+// Rows that can't be read come back as errors along with their line, so the rest can still go ahead
+pub fn read(path: &Path) -> io::Result<Vec<(usize, Result<Row, String>)>> {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+
+    match extension.as_deref() {
+        Some("jsonl") | Some("ndjson") => Ok(read_json_lines(&fs::read_to_string(path)?)),
+        _ => read_csv(File::open(path)?),
+    }
+}
+
+// Only the headers are trimmed, since spaces around the data are part of it, the same as in JSON Lines
+pub fn read_csv<R: io::Read>(input: R) -> io::Result<Vec<(usize, Result<Row, String>)>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::Headers).from_reader(input);
+    let headers = reader.headers().map_err(io::Error::other)?.clone();
+    let mut rows = vec![];
+
+    for record in reader.records() {
+        match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |position| position.line() as usize);
+                let row = record.deserialize(Some(&headers)).map_err(|error| error.to_string());
+                rows.push((line, row));
+            }
+            Err(error) => {
+                let line = error.position().map_or(0, |position| position.line() as usize);
+                rows.push((line, Err(error.to_string())));
+            }
+        }
+    }
+
+    Ok(rows)
+}
+
+// Blank lines are skipped, which keeps the line numbers of the rest
+pub fn read_json_lines(input: &str) -> Vec<(usize, Result<Row, String>)> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, serde_json::from_str(line).map_err(|error| error.to_string())))
+        .collect()
+}
+
+// Where a row's code goes in the output directory, as long as it stays inside it
+// Absolute paths and .. would let a row write anywhere, so they're turned down
+pub fn output_path(directory: &Path, output: &str) -> Result<PathBuf, String> {
+    let relative: PathBuf = Path::new(output)
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => Ok(Some(part)),
+            Component::CurDir => Ok(None),
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                Err(format!("{} has to be a path inside the output directory", output))
+            }
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<_, _>>()?;
+
+    if relative.as_os_str().is_empty() {
+        return Err("output can't be empty".to_string());
+    }
+
+    Ok(directory.join(relative))
+}
+
+// Makes the code for a row and saves it in the output directory
+pub fn generate(row: &Row, settings: &Settings, directory: &Path) -> Result<Metadata, Box<dyn Error>> {
+    let ec_level = match &row.ec {
+        Some(level) => level.parse()?,
+        None => settings.ec_level,
+    };
+
+    let mode = match row.mode.as_deref() {
+        Some("auto") => None,
+        Some(mode) => Some(mode.parse()?),
+        None => settings.mode,
+    };
+
+    let segment = match mode {
+        Some(mode) => Segment::with_mode(mode, row.data.as_bytes())?,
+        None => Segment::auto(row.data.as_bytes()),
+    };
+
+    let mut options = settings.options.clone();
+
+    if let Some(scale) = &row.scale {
+        options.scale = scale.parse()?;
+    }
+
+    options.quiet_zone = row.quiet_zone.unwrap_or(options.quiet_zone);
+
+    for (color, text) in [(&mut options.dark, &row.dark), (&mut options.light, &row.light)] {
+        if let Some(text) = text {
+            *color = render::parse_color(text).ok_or_else(|| format!("{} isn't a color", text))?;
+        }
+    }
+
+    let format = settings
+        .format
+        .or_else(|| Format::from_path(&row.output))
+        .ok_or_else(|| format!("can't tell what format {} should be", row.output))?;
+
+    let code = render::fit(
+        vec![segment],
+        ec_level,
+        row.version.or(settings.version),
+        row.mask.or(settings.mask),
+        settings.bump_ec,
+        &options,
+    )?;
+
+    let path = output_path(directory, &row.output)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    render::write(&code, BufWriter::new(File::create(&path)?), format, &options)?;
+    Ok(code.metadata(false))
+}
+
+// Makes the code for every row in parallel, carrying on past any that fail
+// The outcomes are in the same order as the rows
+pub fn run(rows: &[(usize, Result<Row, String>)], settings: &Settings, directory: &Path) -> Vec<Outcome> {
+    // Rows saving to the same file would overwrite each other in whatever order they finish, so none of them are made
+    let mut lines: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+
+    for (line, row) in rows.iter() {
+        if let Some(path) = row.as_ref().ok().and_then(|row| output_path(directory, &row.output).ok()) {
+            lines.entry(path).or_default().push(*line);
+        }
+    }
+
+    rows.par_iter()
+        .map(|(line, row)| match row {
+            Ok(row) => {
+                let shared = output_path(directory, &row.output)
+                    .ok()
+                    .and_then(|path| lines.get(&path))
+                    .filter(|lines| lines.len() > 1);
+
+                let result = match shared {
+                    Some(lines) => {
+                        let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+                        Err(format!("{} is the output of lines {}", row.output, lines.join(", ")).into())
+                    }
+                    None => generate(row, settings, directory),
+                };
+
+                Outcome {
+                    line: *line,
+                    output: Some(row.output.clone()),
+                    error: result.as_ref().err().map(|error| error.to_string()),
+                    metadata: result.ok(),
+                }
+            }
+            Err(error) => Outcome {
+                line: *line,
+                output: None,
+                error: Some(error.clone()),
+                metadata: None,
+            },
+        })
+        .collect()
+}
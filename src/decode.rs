@@ -0,0 +1,303 @@
+use crate::error::Error;
+use crate::qr::{EcLevel, Metadata, QR};
+use crate::segment::{Mode, Segment, ALPHANUMERIC};
+use image::GrayImage;
+use reed_solomon::Decoder;
+
+// Reads codes back out of clean, upright images like the ones this crate renders
+// The code is found from the box around its dark pixels and the module size is measured off the top edge
+// of the top-left finder, so photos, rotated codes and finders that aren't square are out of reach
+
+// Modules of a code, indexed [y][x], true for dark
+pub type Grid = Vec<Vec<bool>>;
+
+// Anything darker than this counts as a dark module
+const THRESHOLD: u8 = 128;
+
+// Most bits that can be wrong in the format information before it's unreadable
+const MAX_FORMAT_ERRORS: u32 = 3;
+
+// Everything read out of a code, along with how it was made
+#[derive(Clone, Debug)]
+pub struct Decoded {
+    pub version: usize,
+    pub ec_level: EcLevel,
+    pub mask: usize,
+    pub segments: Vec<Segment>,
+    // Codewords that were damaged and had to be restored by error correction
+    pub corrected: usize,
+}
+
+impl Decoded {
+    // The message, which is every segment's data one after another
+    pub fn data(&self) -> Vec<u8> {
+        self.segments.iter().flat_map(|segment| segment.data.iter().copied()).collect()
+    }
+
+    // Describes the code the same way as QR::metadata, by making it again from what was read
+    pub fn metadata(&self) -> Result<Metadata, Error> {
+        let mut code = QR::from_segments(&self.segments, self.ec_level, self.version)?;
+        code.generate_with_mask(self.mask)?;
+        Ok(code.metadata(false))
+    }
+}
+
+// Whether a code can be this many modules wide, which is every size from version 1 to 40
+fn valid_size(size: usize) -> bool {
+    (21..=177).contains(&size) && (size - 17).is_multiple_of(4)
+}
+
+// Reads the data out of an image of a code
+pub fn decode_image(image: &GrayImage) -> Result<Vec<u8>, Error> {
+    read_image(image).map(|decoded| decoded.data())
+}
+
+// Reads the data out of an image of a code, keeping its version, EC level, mask and segments
+pub fn read_image(image: &GrayImage) -> Result<Decoded, Error> {
+    read_grid(&sample(image)?)
+}
+
+// Finds the code in an image and reads the color of each module from the middle of it
+pub fn sample(image: &GrayImage) -> Result<Grid, Error> {
+    let dark = |x: u32, y: u32| image.get_pixel(x, y)[0] < THRESHOLD;
+
+    // Box around every dark pixel, which is the edge of the code since the quiet zone is all light
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[0] < THRESHOLD {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+    }
+
+    if left > right {
+        return Err(Error::NotFound);
+    }
+
+    // The top edge of the top-left finder is 7 modules of solid dark
+    let mut run = 0;
+
+    while left + run <= right && dark(left + run, top) {
+        run += 1;
+    }
+
+    let width = (right - left + 1) as f64;
+    let size = (width / (run as f64 / 7.0)).round() as usize;
+
+    if !valid_size(size) || bottom - top != right - left {
+        return Err(Error::NotFound);
+    }
+
+    // Measure the module size again over the whole code, which is far more accurate
+    let module = width / size as f64;
+
+    Ok((0..size)
+        .map(|y| {
+            (0..size)
+                .map(|x| {
+                    let pixel_x = left + ((x as f64 + 0.5) * module) as u32;
+                    let pixel_y = top + ((y as f64 + 0.5) * module) as u32;
+                    dark(pixel_x, pixel_y)
+                })
+                .collect()
+        })
+        .collect())
+}
+
+// Reads the EC level and mask from the format information, trying the copy at the top-left first
+fn read_format(grid: &Grid) -> Result<(EcLevel, usize), Error> {
+    let size = grid.len();
+    let bit = |x: usize, y: usize| grid[y][x] as usize;
+
+    let mut top_left = 0;
+
+    for i in 0..6 {
+        top_left |= bit(8, i) << i;
+    }
+
+    top_left |= bit(8, 7) << 6;
+    top_left |= bit(8, 8) << 7;
+    top_left |= bit(7, 8) << 8;
+
+    for i in 9..15 {
+        top_left |= bit(14 - i, 8) << i;
+    }
+
+    // The second copy is split between the top right and bottom left
+    let mut split = 0;
+
+    for i in 0..8 {
+        split |= bit(size - 1 - i, 8) << i;
+    }
+
+    for i in 8..15 {
+        split |= bit(8, size - 15 + i) << i;
+    }
+
+    // Pick whichever valid format string is closest to what was read
+    let mut best = None;
+    let mut best_errors = u32::MAX;
+
+    for &ec_level in [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H].iter() {
+        for mask in 0..8 {
+            let format_string = QR::format_string(ec_level, mask);
+            let errors = (format_string ^ top_left).count_ones().min((format_string ^ split).count_ones());
+
+            if errors < best_errors {
+                best = Some((ec_level, mask));
+                best_errors = errors;
+            }
+        }
+    }
+
+    match best {
+        Some(format) if best_errors <= MAX_FORMAT_ERRORS => Ok(format),
+        _ => Err(Error::Unreadable),
+    }
+}
+
+// Reads the data out of a grid of modules
+pub fn decode_grid(grid: &Grid) -> Result<Vec<u8>, Error> {
+    read_grid(grid).map(|decoded| decoded.data())
+}
+
+pub fn read_grid(grid: &Grid) -> Result<Decoded, Error> {
+    let size = grid.len();
+
+    if !valid_size(size) || grid.iter().any(|row| row.len() != size) {
+        return Err(Error::NotFound);
+    }
+
+    let version = (size - 17) / 4;
+    let (ec_level, mask) = read_format(grid)?;
+
+    // A blank code of the same version knows where every bit of the payload goes
+    let template = QR::blank(version, ec_level);
+    let mut payload = vec![0u8; template.payload_len()];
+
+    for (y, row) in grid.iter().enumerate() {
+        for (x, &dark) in row.iter().enumerate() {
+            if let Some((codeword, bit)) = template.payload_bit_at(x, y) {
+                if dark != QR::mask_applies(mask, x, y) {
+                    payload[codeword] |= 1 << bit;
+                }
+            }
+        }
+    }
+
+    // Undo the interleaving, each block ends up as its data codewords followed by its EC codewords
+    let (block_count, ec_per_block) = template.blocks();
+    let mut blocks = vec![vec![]; block_count];
+
+    for (codeword, &value) in payload.iter().enumerate() {
+        blocks[template.payload_block(codeword)].push(value);
+    }
+
+    let decoder = Decoder::new(ec_per_block);
+    let mut data = vec![];
+    let mut corrected = 0;
+
+    for block in blocks {
+        let fixed = decoder.correct(&block, None).map_err(|_| Error::TooDamaged)?;
+        corrected += block.iter().zip(fixed.iter()).filter(|(a, b)| a != b).count();
+        data.extend_from_slice(fixed.data());
+    }
+
+    Ok(Decoded {
+        version,
+        ec_level,
+        mask,
+        segments: read_segments(&data, version)?,
+        corrected,
+    })
+}
+
+// Reads the bits of the data one field at a time
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn read(&mut self, bits: usize) -> Result<usize, Error> {
+        if bits > self.remaining() {
+            return Err(Error::TooDamaged);
+        }
+
+        let mut value = 0;
+
+        for _ in 0..bits {
+            let bit = (self.data[self.position / 8] >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as usize;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+// Pulls the segments out of the data codewords, stopping at the terminator
+fn read_segments(data: &[u8], version: usize) -> Result<Vec<Segment>, Error> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut segments = vec![];
+
+    // The terminator can be cut short, or left out, if the data fills the code
+    while reader.remaining() >= 4 {
+        let indicator = reader.read(4)?;
+
+        if indicator == 0 {
+            break;
+        }
+
+        let mode = Mode::from_indicator(indicator).ok_or(Error::UnsupportedMode(indicator))?;
+        let mut count = reader.read(mode.char_count_bits(version))?;
+        let mut message = vec![];
+
+        match mode {
+            Mode::Numeric => {
+                // Groups of 3 digits, with the last group being shorter if the count isn't a multiple of 3
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = reader.read([0, 4, 7, 10][digits])?;
+
+                    if value >= 10usize.pow(digits as u32) {
+                        return Err(Error::TooDamaged);
+                    }
+
+                    message.extend(format!("{:0width$}", value, width = digits).bytes());
+                    count -= digits;
+                }
+            }
+            Mode::Alphanumeric => {
+                let alphanumeric = ALPHANUMERIC.as_bytes();
+
+                while count >= 2 {
+                    let value = reader.read(11)?;
+                    message.push(*alphanumeric.get(value / 45).ok_or(Error::TooDamaged)?);
+                    message.push(alphanumeric[value % 45]);
+                    count -= 2;
+                }
+
+                if count == 1 {
+                    message.push(*alphanumeric.get(reader.read(6)?).ok_or(Error::TooDamaged)?);
+                }
+            }
+            Mode::Byte => {
+                for _ in 0..count {
+                    message.push(reader.read(8)? as u8);
+                }
+            }
+        }
+
+        segments.push(Segment { mode, data: message });
+    }
+
+    Ok(segments)
+}
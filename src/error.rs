@@ -0,0 +1,59 @@
+use crate::qr::EcLevel;
+use alloc::string::String;
+use core::fmt;
+
+// Everything that can stop a code from being made
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // The message doesn't fit in any version, which holds at most this many bytes at the EC level
+    TooLong { max: usize },
+    // The message doesn't fit in the version it was asked to be made at
+    TooLongForVersion(usize),
+    // The logo covers more codewords in one block than error correction can restore
+    LogoTooBig { damaged: usize, correctable: usize },
+    // There's no code in the image, or it couldn't be lined up
+    NotFound,
+    // The format information is too damaged to tell how the code was made
+    Unreadable,
+    // More codewords are damaged than error correction can restore
+    TooDamaged,
+    // The data uses a mode that can't be read yet
+    UnsupportedMode(usize),
+    // A payload was built without a field its format needs
+    MissingField(&'static str),
+    // A payload field has a value its format doesn't allow
+    InvalidField { field: &'static str, reason: String },
+    // A payload is longer than its format allows, in bytes
+    PayloadTooLong { length: usize, max: usize },
+    // The payload's format only allows one EC level
+    WrongEcLevel { required: EcLevel },
+    // The image would be wider than this many pixels
+    ImageTooLarge { max: u32 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::TooLong { max } => write!(f, "message is too long, codes hold at most {} bytes at this EC level", max),
+            Error::TooLongForVersion(version) => write!(f, "message is too long for a version {} code", version),
+            Error::LogoTooBig { damaged, correctable } => write!(
+                f,
+                "logo covers {} codewords in one block, but only {} can be corrected",
+                damaged, correctable
+            ),
+            Error::NotFound => write!(f, "no code found"),
+            Error::Unreadable => write!(f, "format information is unreadable"),
+            Error::TooDamaged => write!(f, "code is too damaged to read"),
+            Error::UnsupportedMode(mode) => write!(f, "unsupported data mode {:04b}", mode),
+            Error::MissingField(field) => write!(f, "missing {}", field),
+            Error::InvalidField { field, reason } => write!(f, "invalid {}: {}", field, reason),
+            Error::PayloadTooLong { length, max } => {
+                write!(f, "payload is {} bytes, but the format allows at most {}", length, max)
+            }
+            Error::WrongEcLevel { required } => write!(f, "this format requires EC level {:?}", required),
+            Error::ImageTooLarge { max } => write!(f, "image would be more than {} pixels wide", max),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
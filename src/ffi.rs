@@ -0,0 +1,476 @@
+use crate::error::Error;
+use crate::qr::{EcLevel, QR};
+use crate::render::{self, Format, RenderOptions, Scale};
+use crate::segment::{Mode, Segment};
+use image::Rgba;
+use std::convert::TryFrom;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+// C ABI for embedding the encoder in C and C++ programs, built as libqr.a or libqr.so
+// include/qr.h is generated from this file with cbindgen, so rerun it after changing anything here:
+//   cbindgen --config cbindgen.toml --output include/qr.h
+// Everything is passed as plain integers and pointers, and symbols are only ever touched through a pointer
+// Enums coming in from C are taken as uint32_t and checked, since a value outside an enum is undefined in Rust
+// Comments on the items are doc comments so that they end up in the header too
+
+/// What a call came to, with a value for every variant of the library's Error and a few for the ABI itself
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QrStatus {
+    Ok = 0,
+    TooLong,
+    TooLongForVersion,
+    LogoTooBig,
+    NotFound,
+    Unreadable,
+    TooDamaged,
+    UnsupportedMode,
+    MissingField,
+    InvalidField,
+    PayloadTooLong,
+    WrongEcLevel,
+    /// A pointer that has to be given was null
+    NullPointer,
+    /// The buffer can't hold everything, the length needed has been written out instead
+    BufferTooSmall,
+    /// The image couldn't be encoded
+    RenderFailed,
+    /// The scale and quiet zone make the image too big to draw, which comes after the ABI's own values to keep theirs
+    ImageTooLarge,
+}
+
+// The error for a number from C that isn't one of an enum's values
+fn unknown(field: &'static str, value: u32) -> Error {
+    Error::InvalidField {
+        field,
+        reason: format!("{} isn't one of its values", value),
+    }
+}
+
+impl TryFrom<u32> for QrStatus {
+    type Error = Error;
+
+    fn try_from(status: u32) -> Result<QrStatus, Error> {
+        Ok(match status {
+            0 => QrStatus::Ok,
+            1 => QrStatus::TooLong,
+            2 => QrStatus::TooLongForVersion,
+            3 => QrStatus::LogoTooBig,
+            4 => QrStatus::NotFound,
+            5 => QrStatus::Unreadable,
+            6 => QrStatus::TooDamaged,
+            7 => QrStatus::UnsupportedMode,
+            8 => QrStatus::MissingField,
+            9 => QrStatus::InvalidField,
+            10 => QrStatus::PayloadTooLong,
+            11 => QrStatus::WrongEcLevel,
+            12 => QrStatus::NullPointer,
+            13 => QrStatus::BufferTooSmall,
+            14 => QrStatus::RenderFailed,
+            15 => QrStatus::ImageTooLarge,
+            _ => return Err(unknown("status", status)),
+        })
+    }
+}
+
+impl From<&Error> for QrStatus {
+    fn from(error: &Error) -> QrStatus {
+        match error {
+            Error::TooLong { .. } => QrStatus::TooLong,
+            Error::TooLongForVersion(_) => QrStatus::TooLongForVersion,
+            Error::LogoTooBig { .. } => QrStatus::LogoTooBig,
+            Error::NotFound => QrStatus::NotFound,
+            Error::Unreadable => QrStatus::Unreadable,
+            Error::TooDamaged => QrStatus::TooDamaged,
+            Error::UnsupportedMode(_) => QrStatus::UnsupportedMode,
+            Error::MissingField(_) => QrStatus::MissingField,
+            Error::InvalidField { .. } => QrStatus::InvalidField,
+            Error::PayloadTooLong { .. } => QrStatus::PayloadTooLong,
+            Error::WrongEcLevel { .. } => QrStatus::WrongEcLevel,
+            Error::ImageTooLarge { .. } => QrStatus::ImageTooLarge,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QrEcLevel {
+    L = 0,
+    M,
+    Q,
+    H,
+}
+
+impl TryFrom<u32> for QrEcLevel {
+    type Error = Error;
+
+    fn try_from(level: u32) -> Result<QrEcLevel, Error> {
+        Ok(match level {
+            0 => QrEcLevel::L,
+            1 => QrEcLevel::M,
+            2 => QrEcLevel::Q,
+            3 => QrEcLevel::H,
+            _ => return Err(unknown("EC level", level)),
+        })
+    }
+}
+
+impl From<QrEcLevel> for EcLevel {
+    fn from(level: QrEcLevel) -> EcLevel {
+        match level {
+            QrEcLevel::L => EcLevel::L,
+            QrEcLevel::M => EcLevel::M,
+            QrEcLevel::Q => EcLevel::Q,
+            QrEcLevel::H => EcLevel::H,
+        }
+    }
+}
+
+impl From<EcLevel> for QrEcLevel {
+    fn from(level: EcLevel) -> QrEcLevel {
+        match level {
+            EcLevel::L => QrEcLevel::L,
+            EcLevel::M => QrEcLevel::M,
+            EcLevel::Q => QrEcLevel::Q,
+            EcLevel::H => QrEcLevel::H,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QrMode {
+    /// Densest mode that holds all of the data
+    Auto = 0,
+    Numeric,
+    Alphanumeric,
+    Byte,
+}
+
+impl TryFrom<u32> for QrMode {
+    type Error = Error;
+
+    fn try_from(mode: u32) -> Result<QrMode, Error> {
+        Ok(match mode {
+            0 => QrMode::Auto,
+            1 => QrMode::Numeric,
+            2 => QrMode::Alphanumeric,
+            3 => QrMode::Byte,
+            _ => return Err(unknown("mode", mode)),
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QrFormat {
+    Png = 0,
+    Svg,
+    Jpeg,
+    Bmp,
+    Gif,
+}
+
+impl TryFrom<u32> for QrFormat {
+    type Error = Error;
+
+    fn try_from(format: u32) -> Result<QrFormat, Error> {
+        Ok(match format {
+            0 => QrFormat::Png,
+            1 => QrFormat::Svg,
+            2 => QrFormat::Jpeg,
+            3 => QrFormat::Bmp,
+            4 => QrFormat::Gif,
+            _ => return Err(unknown("format", format)),
+        })
+    }
+}
+
+impl From<QrFormat> for Format {
+    fn from(format: QrFormat) -> Format {
+        match format {
+            QrFormat::Png => Format::Png,
+            QrFormat::Svg => Format::Svg,
+            QrFormat::Jpeg => Format::Jpeg,
+            QrFormat::Bmp => Format::Bmp,
+            QrFormat::Gif => Format::Gif,
+        }
+    }
+}
+
+/// How to make and draw a symbol, start from qr_options_default and change what's needed
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct QrOptions {
+    /// One of QrEcLevel
+    pub ec_level: u32,
+    /// One of QrMode
+    pub mode: u32,
+    /// 1 to 40, or 0 for the smallest that fits
+    pub version: u32,
+    /// 0 to 7, or -1 for the one with the lowest penalty
+    pub mask: i32,
+    /// Pixels per module in raster images, SVGs use it for their width and height
+    pub scale: u32,
+    /// Width of the light border in modules
+    pub quiet_zone: u32,
+    /// Colors as 0xRRGGBBAA
+    pub dark: u32,
+    pub light: u32,
+}
+
+impl QrOptions {
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            scale: Scale::PerModule(self.scale),
+            quiet_zone: self.quiet_zone,
+            dark: Rgba(self.dark.to_be_bytes()),
+            light: Rgba(self.light.to_be_bytes()),
+            ..RenderOptions::default()
+        }
+    }
+}
+
+/// A generated code, opaque to C
+pub struct QrSymbol {
+    code: QR,
+    options: RenderOptions,
+}
+
+/// The same defaults as the command line: EC level Q, any mode, version and mask, 8 pixels per module and black on white
+#[no_mangle]
+pub extern "C" fn qr_options_default() -> QrOptions {
+    QrOptions {
+        ec_level: QrEcLevel::Q as u32,
+        mode: QrMode::Auto as u32,
+        version: 0,
+        mask: -1,
+        scale: 8,
+        quiet_zone: render::MIN_QUIET_ZONE,
+        dark: 0x000000ff,
+        light: 0xffffffff,
+    }
+}
+
+fn make(data: &[u8], options: &QrOptions) -> Result<QrSymbol, Error> {
+    let ec_level = QrEcLevel::try_from(options.ec_level)?;
+
+    let segment = match QrMode::try_from(options.mode)? {
+        QrMode::Auto => Segment::auto(data),
+        QrMode::Numeric => Segment::with_mode(Mode::Numeric, data)?,
+        QrMode::Alphanumeric => Segment::with_mode(Mode::Alphanumeric, data)?,
+        QrMode::Byte => Segment::bytes(data),
+    };
+
+    let version = match options.version {
+        0 => None,
+        version => Some(version as usize),
+    };
+
+    let mask = match options.mask {
+        -1 => None,
+        mask if mask < 0 => {
+            return Err(Error::InvalidField {
+                field: "mask",
+                reason: format!("{} isn't between 0 and 7", mask),
+            })
+        }
+        mask => Some(mask as usize),
+    };
+
+    let render_options = options.render_options();
+    let code = render::fit(vec![segment], ec_level.into(), version, mask, false, &render_options)?;
+
+    Ok(QrSymbol {
+        code,
+        options: render_options,
+    })
+}
+
+/// Makes a symbol out of length bytes of data, which is written to out and has to be freed with qr_symbol_free
+/// options can be null for the defaults, and out is left alone if anything goes wrong
+///
+/// # Safety
+/// data has to point to length readable bytes, options to a QrOptions if it isn't null, and out to a writable pointer
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_new(
+    data: *const u8,
+    length: usize,
+    options: *const QrOptions,
+    out: *mut *mut QrSymbol,
+) -> QrStatus {
+    if (data.is_null() && length > 0) || out.is_null() {
+        return QrStatus::NullPointer;
+    }
+
+    let data = if length == 0 { &[][..] } else { slice::from_raw_parts(data, length) };
+    let options = options.as_ref().copied().unwrap_or_else(|| qr_options_default());
+
+    match make(data, &options) {
+        Ok(symbol) => {
+            *out = Box::into_raw(Box::new(symbol));
+            QrStatus::Ok
+        }
+        Err(error) => QrStatus::from(&error),
+    }
+}
+
+/// # Safety
+/// symbol has to have come from qr_symbol_new and not been freed yet, or be null
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_free(symbol: *mut QrSymbol) {
+    if !symbol.is_null() {
+        drop(Box::from_raw(symbol));
+    }
+}
+
+/// Modules along each side, not counting the quiet zone, or 0 for a null symbol
+///
+/// # Safety
+/// symbol has to be a live symbol from qr_symbol_new, or null
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_size(symbol: *const QrSymbol) -> usize {
+    symbol.as_ref().map_or(0, |symbol| symbol.code.size)
+}
+
+/// # Safety
+/// symbol has to be a live symbol from qr_symbol_new, or null
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_version(symbol: *const QrSymbol) -> u32 {
+    symbol.as_ref().map_or(0, |symbol| symbol.code.version as u32)
+}
+
+/// # Safety
+/// symbol has to be a live symbol from qr_symbol_new, or null
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_mask(symbol: *const QrSymbol) -> i32 {
+    symbol.as_ref().map_or(-1, |symbol| symbol.code.mask as i32)
+}
+
+/// # Safety
+/// symbol has to be a live symbol from qr_symbol_new, or null
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_ec_level(symbol: *const QrSymbol) -> QrEcLevel {
+    symbol.as_ref().map_or(QrEcLevel::L, |symbol| symbol.code.ec_level.into())
+}
+
+/// Whether the module at column x, row y is dark, which is never the case outside the symbol
+///
+/// # Safety
+/// symbol has to be a live symbol from qr_symbol_new, or null
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_is_dark(symbol: *const QrSymbol, x: usize, y: usize) -> bool {
+    symbol
+        .as_ref()
+        .is_some_and(|symbol| x < symbol.code.size && y < symbol.code.size && symbol.code.is_dark(x, y))
+}
+
+/// Copies every module into buffer row by row, 1 for dark and 0 for light, which takes size * size bytes
+///
+/// # Safety
+/// symbol has to be a live symbol from qr_symbol_new, and buffer has to point to length writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_modules(symbol: *const QrSymbol, buffer: *mut u8, length: usize) -> QrStatus {
+    let symbol = match symbol.as_ref() {
+        Some(symbol) => symbol,
+        None => return QrStatus::NullPointer,
+    };
+
+    let size = symbol.code.size;
+
+    if length < size * size {
+        return QrStatus::BufferTooSmall;
+    }
+
+    if buffer.is_null() {
+        return QrStatus::NullPointer;
+    }
+
+    let buffer = slice::from_raw_parts_mut(buffer, size * size);
+
+    for y in 0..size {
+        for x in 0..size {
+            buffer[y * size + x] = symbol.code.is_dark(x, y) as u8;
+        }
+    }
+
+    QrStatus::Ok
+}
+
+/// Draws the symbol in a format and copies the file into buffer, writing how many bytes it takes to written
+/// Call it with a null buffer first to find out how big the buffer has to be
+/// format is one of QrFormat, anything else gives QR_STATUS_INVALID_FIELD
+///
+/// # Safety
+/// symbol has to be a live symbol from qr_symbol_new, buffer has to point to capacity writable bytes unless it's
+/// null, and written has to point to a writable size_t
+#[no_mangle]
+pub unsafe extern "C" fn qr_symbol_render(
+    symbol: *const QrSymbol,
+    format: u32,
+    buffer: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> QrStatus {
+    let symbol = match symbol.as_ref() {
+        Some(symbol) => symbol,
+        None => return QrStatus::NullPointer,
+    };
+
+    if written.is_null() {
+        return QrStatus::NullPointer;
+    }
+
+    let format = match QrFormat::try_from(format) {
+        Ok(format) => format,
+        Err(error) => return QrStatus::from(&error),
+    };
+
+    let mut file = vec![];
+
+    if render::write(&symbol.code, &mut file, format.into(), &symbol.options).is_err() {
+        return QrStatus::RenderFailed;
+    }
+
+    *written = file.len();
+
+    if buffer.is_null() || capacity < file.len() {
+        return QrStatus::BufferTooSmall;
+    }
+
+    ptr::copy_nonoverlapping(file.as_ptr(), buffer, file.len());
+    QrStatus::Ok
+}
+
+/// What a status means in English, as a static string that mustn't be freed
+/// status is one of QrStatus, anything else gets a message saying so
+#[no_mangle]
+pub extern "C" fn qr_status_message(status: u32) -> *const c_char {
+    let status = match QrStatus::try_from(status) {
+        Ok(status) => status,
+        Err(_) => return b"unknown status\0".as_ptr() as *const c_char,
+    };
+
+    let message: &'static [u8] = match status {
+        QrStatus::Ok => b"ok\0",
+        QrStatus::TooLong => b"message is too long for any version\0",
+        QrStatus::TooLongForVersion => b"message is too long for the version\0",
+        QrStatus::LogoTooBig => b"logo covers more codewords than can be corrected\0",
+        QrStatus::NotFound => b"no code found\0",
+        QrStatus::Unreadable => b"format information is unreadable\0",
+        QrStatus::TooDamaged => b"code is too damaged to read\0",
+        QrStatus::UnsupportedMode => b"unsupported data mode\0",
+        QrStatus::MissingField => b"missing field\0",
+        QrStatus::InvalidField => b"invalid field\0",
+        QrStatus::PayloadTooLong => b"payload is too long\0",
+        QrStatus::WrongEcLevel => b"wrong EC level\0",
+        QrStatus::NullPointer => b"null pointer\0",
+        QrStatus::BufferTooSmall => b"buffer is too small\0",
+        QrStatus::RenderFailed => b"image couldn't be encoded\0",
+        QrStatus::ImageTooLarge => b"image is too large\0",
+    };
+
+    message.as_ptr() as *const c_char
+}
@@ -0,0 +1,35 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+// A grid of anything, indexed (x, y) like the module matrix
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn filled_with(value: T, width: usize, height: usize) -> Grid<T> {
+        Grid {
+            cells: vec![value; width * height],
+            width,
+        }
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(x < self.width, "column {} is out of bounds", x);
+        &self.cells[y * self.width + x]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(x < self.width, "column {} is out of bounds", x);
+        &mut self.cells[y * self.width + x]
+    }
+}
@@ -0,0 +1,25 @@
+// The encoder itself (segments, error correction, placement and masking) only needs alloc
+// Rendering, reading codes, payloads and everything that does I/O need the std feature, which is on by default
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod decode;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod ffi;
+mod grid;
+mod matrix;
+#[cfg(feature = "std")]
+pub mod payload;
+pub mod qr;
+#[cfg(feature = "std")]
+pub mod render;
+pub mod segment;
+#[cfg(feature = "std")]
+pub mod serve;
+#[cfg(all(target_arch = "wasm32", feature = "std"))]
+pub mod wasm;
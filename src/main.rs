@@ -1,19 +1,708 @@
-mod qr;
-
-use crate::qr::qr::QR;
-use std::env;
-
-fn main() {
-    // Basic command-line parser
-    // TODO: replace with something c o o l e r 
-    let args: Vec<String> = env::args().collect();
-    let input = args[1].clone();
-    let mut code = QR::new(input);
-    code.generate();
-
-    if args.len() == 3 {
-        code.save_image(args[2].clone(), 1000)
-    } else if args.len() > 3 {
-        code.save_image(args[2].clone(), args[3].parse().unwrap())
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use qr::batch;
+use qr::decode;
+use qr::error::Error;
+use qr::payload::epc::{Epc, Remittance};
+use qr::payload::event::{Event, Time};
+use qr::payload::geo::Geo;
+use qr::payload::message::{Mailto, Sms, Tel};
+use qr::payload::otp::{self, Algorithm, Kind, Otp};
+use qr::payload::wifi::{Eap, Security, Wifi};
+use qr::payload::{self, Payload};
+use qr::qr::EcLevel;
+use qr::render::logo::Logo;
+use qr::render::{self, rgb, Format, RenderOptions, Scale};
+use qr::segment::{Mode, Segment};
+use qr::serve;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[command(name = "qr", about = "Generates QR codes, and reads back the ones it makes", disable_version_flag = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Encode text into a code")]
+    Encode {
+        #[arg(help = "Text to encode, or - to read the data from stdin [default: stdin]")]
+        input: Option<String>,
+        #[arg(long, conflicts_with = "input", help = "File to read the data from, which can be binary")]
+        file: Option<String>,
+        #[arg(long, value_enum, default_value_t = ModeArg::Auto, help = "How the text is packed into the code")]
+        mode: ModeArg,
+        #[arg(
+            long,
+            conflicts_with_all = ["mode", "version", "mask", "logo", "logo_size", "halftone"],
+            help = "Split the input across three codes in the red, green and blue channels"
+        )]
+        rgb: bool,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "Make a code for every row of a CSV or JSON Lines file")]
+    Batch {
+        #[arg(
+            help = "CSV or JSON Lines (.jsonl) file with data and output columns, \
+                    and optionally ec, mode, version, mask, scale, quiet_zone, dark and light"
+        )]
+        file: String,
+        #[arg(long, default_value = ".", help = "Directory the outputs are saved in")]
+        out_dir: String,
+        #[arg(long, value_enum, default_value_t = ModeArg::Auto, help = "How the data is packed into the codes")]
+        mode: ModeArg,
+        #[arg(short, long, help = "Codes to make at once [default: one per CPU]")]
+        jobs: Option<usize>,
+        #[arg(long, help = "File to write how every row went to, as JSON Lines")]
+        report: Option<String>,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "Run an HTTP server making codes for requests like GET /qr?data=hello&format=svg&ec=H")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080", help = "Address to listen on")]
+        address: String,
+        #[arg(long, default_value_t = 4, help = "Requests to handle at once")]
+        threads: usize,
+        #[arg(long, help = "Most bytes of data accepted [default: as much as a code can hold]")]
+        max_data: Option<usize>,
+        #[arg(long, default_value_t = 4096, help = "Widest image that can be asked for, in pixels")]
+        max_size: u32,
+        #[arg(long, default_value_t = 86_400, help = "Seconds clients can cache codes for")]
+        max_age: u32,
+    },
+    #[command(about = "Read the data out of an image of a code")]
+    Decode {
+        #[arg(help = "Image of the code")]
+        image: String,
+        #[arg(long, help = "Read the three codes out of the channels of an RGB code")]
+        rgb: bool,
+    },
+    #[command(about = "Show the version, EC level, mask and segments of a code in an image")]
+    Inspect {
+        #[arg(help = "Image of the code")]
+        image: String,
+        #[arg(long, help = "Print it as JSON")]
+        json: bool,
+    },
+    #[command(about = "Wi-Fi network login")]
+    Wifi {
+        #[arg(long, help = "Network name")]
+        ssid: String,
+        #[arg(long)]
+        password: Option<String>,
+        #[arg(long, default_value = "wpa", help = "open, wep, wpa or enterprise")]
+        security: Security,
+        #[arg(long, help = "The network doesn't broadcast its name")]
+        hidden: bool,
+        #[arg(long, help = "EAP method for enterprise networks, like PEAP or TTLS")]
+        eap: Option<String>,
+        #[arg(long, requires = "eap", help = "Phase 2 authentication, like MSCHAPV2")]
+        phase2: Option<String>,
+        #[arg(long, requires = "eap")]
+        identity: Option<String>,
+        #[arg(long, requires = "eap")]
+        anonymous_identity: Option<String>,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "EPC SEPA credit transfer, for European banking apps")]
+    Epc {
+        #[arg(long, help = "Name of who's being paid")]
+        name: String,
+        #[arg(long)]
+        iban: String,
+        #[arg(long)]
+        bic: Option<String>,
+        #[arg(long, value_parser = payload::parse_amount, help = "Amount in euros, like 12.50")]
+        amount: Option<u64>,
+        #[arg(long, help = "Four letter purpose code")]
+        purpose: Option<String>,
+        #[arg(long, conflicts_with = "text", help = "Structured creditor reference")]
+        reference: Option<String>,
+        #[arg(long, help = "Unstructured remittance text")]
+        text: Option<String>,
+        #[arg(long, help = "Note to the payer")]
+        information: Option<String>,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "Calendar event")]
+    Event {
+        #[arg(long)]
+        summary: String,
+        #[arg(long, help = "Date or time like 2024-05-01, 2024-05-01T09:30 or 2024-05-01T09:30+02:00")]
+        start: Time,
+        #[arg(long)]
+        end: Option<Time>,
+        #[arg(long, help = "Time zone for times without an offset, like Europe/Zurich")]
+        tz: Option<String>,
+        #[arg(long)]
+        location: Option<String>,
+        #[arg(long)]
+        description: Option<String>,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "Location")]
+    Geo {
+        #[arg(long, allow_negative_numbers = true)]
+        lat: f64,
+        #[arg(long, allow_negative_numbers = true)]
+        lon: f64,
+        #[arg(long, allow_negative_numbers = true, help = "Altitude in metres")]
+        altitude: Option<f64>,
+        #[arg(long, help = "Name or address of the place")]
+        query: Option<String>,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "Text message")]
+    Sms {
+        #[arg(long)]
+        number: String,
+        #[arg(long)]
+        message: Option<String>,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "Phone number")]
+    Tel {
+        #[arg(long)]
+        number: String,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "Email")]
+    Mailto {
+        #[arg(long, value_delimiter = ',', help = "Comma separated recipients")]
+        to: Vec<String>,
+        #[arg(long, value_delimiter = ',')]
+        cc: Vec<String>,
+        #[arg(long, value_delimiter = ',')]
+        bcc: Vec<String>,
+        #[arg(long)]
+        subject: Option<String>,
+        #[arg(long)]
+        body: Option<String>,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+    #[command(about = "One-time password account for authenticator apps")]
+    Otp {
+        #[arg(long)]
+        account: String,
+        #[arg(long)]
+        issuer: Option<String>,
+        #[arg(long, help = "Base32 secret, a new one is made and printed if there isn't one")]
+        secret: Option<String>,
+        #[arg(long, conflicts_with = "period", help = "Make a counter-based code starting from this counter")]
+        counter: Option<u64>,
+        #[arg(long, default_value_t = 30, help = "Seconds each time-based code lasts")]
+        period: u32,
+        #[arg(long, default_value = "sha1", help = "sha1, sha256 or sha512")]
+        algorithm: Algorithm,
+        #[arg(long, default_value_t = 6)]
+        digits: u32,
+        #[command(flatten)]
+        code: CodeArgs,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ModeArg {
+    Auto,
+    Numeric,
+    Alphanumeric,
+    Byte,
+}
+
+impl ModeArg {
+    // The mode to use, or None for the densest one the data fits
+    fn mode(self) -> Option<Mode> {
+        match self {
+            ModeArg::Auto => None,
+            ModeArg::Numeric => Some(Mode::Numeric),
+            ModeArg::Alphanumeric => Some(Mode::Alphanumeric),
+            ModeArg::Byte => Some(Mode::Byte),
+        }
+    }
+}
+
+// Options for how the code is made and drawn, shared by every subcommand that makes one
+#[derive(Args)]
+struct CodeArgs {
+    #[arg(short, long, help = "File to save the code to, printed to the terminal if there isn't one")]
+    output: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "png, svg, jpeg, bmp, gif, terminal or compact [default: from the output's extension, or terminal]"
+    )]
+    format: Option<Format>,
+    #[arg(short, long = "ec", value_name = "LEVEL", help = "Error correction level, L, M, Q or H [default: Q]")]
+    ec_level: Option<EcLevel>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=40),
+        conflicts_with_all = ["logo", "logo_size", "halftone"],
+        help = "Version to make the code at, from 1 to 40 [default: the smallest that fits]"
+    )]
+    version: Option<u8>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(0..8),
+        conflicts_with = "halftone",
+        help = "Mask pattern, from 0 to 7 [default: the one with the lowest penalty]"
+    )]
+    mask: Option<u8>,
+    #[arg(short, long, default_value = "1000", help = "Image size in pixels, or pixels per module like 10x")]
+    scale: Scale,
+    #[arg(long, default_value_t = render::MIN_QUIET_ZONE, help = "Width of the border in modules")]
+    quiet_zone: u32,
+    #[arg(long, help = "Resolution to record in PNGs")]
+    dpi: Option<u32>,
+    #[arg(long, value_parser = parse_color, help = "Color of the dark modules, like #1a2b3c")]
+    dark: Option<image::Rgba<u8>>,
+    #[arg(long, value_parser = parse_color, help = "Color of the light modules and border, or transparent")]
+    light: Option<image::Rgba<u8>>,
+    #[arg(long, help = "Module shape, square, circle, rounded or connected")]
+    shape: Option<render::shape::ModuleShape>,
+    #[arg(long, help = "Shape of the finders' outer rings, square, circle or rounded")]
+    finder_ring: Option<render::shape::FinderShape>,
+    #[arg(long, help = "Shape of the finders' eyes, square, circle or rounded")]
+    finder_eye: Option<render::shape::FinderShape>,
+    #[arg(long, help = "Image to put in the middle of the code")]
+    logo: Option<String>,
+    #[arg(long, help = "Fraction of the code the logo covers, which clears the middle even without a logo")]
+    logo_size: Option<f64>,
+    #[arg(long, help = "Raise the EC level until the code can be read with the logo on top")]
+    bump_ec: bool,
+    #[arg(long, help = "Draw the Swiss cross over the middle, for QR-bills")]
+    swiss_cross: bool,
+    #[arg(long, help = "Picture to show through the modules")]
+    halftone: Option<String>,
+    #[arg(long, help = "Print what the code ended up as in JSON, instead of drawing it in the terminal")]
+    json: bool,
+    #[arg(long, requires = "json", help = "Include every module in the JSON")]
+    matrix: bool,
+}
+
+fn parse_color(text: &str) -> std::result::Result<image::Rgba<u8>, String> {
+    render::parse_color(text).ok_or_else(|| format!("{} isn't a color like #1a2b3c or #1a2b3c80", text))
+}
+
+impl CodeArgs {
+    fn ec_level(&self, required: Option<EcLevel>) -> Result<EcLevel> {
+        match (required, self.ec_level) {
+            // Formats with their own EC level don't let it be picked
+            (Some(required), Some(level)) if level != required => Err(Error::WrongEcLevel { required }.into()),
+            (Some(required), _) => Ok(required),
+            (None, level) => Ok(level.unwrap_or(EcLevel::Q)),
+        }
+    }
+
+    fn render_options(&self) -> Result<RenderOptions> {
+        let mut options = RenderOptions {
+            scale: self.scale,
+            quiet_zone: self.quiet_zone,
+            dpi: self.dpi,
+            swiss_cross: self.swiss_cross,
+            ..RenderOptions::default()
+        };
+
+        options.dark = self.dark.unwrap_or(options.dark);
+        options.light = self.light.unwrap_or(options.light);
+        options.module_shape = self.shape.unwrap_or(options.module_shape);
+        options.finder_ring = self.finder_ring.unwrap_or(options.finder_ring);
+        options.finder_eye = self.finder_eye.unwrap_or(options.finder_eye);
+
+        // A logo size on its own just clears the middle of the code
+        if self.logo.is_some() || self.logo_size.is_some() {
+            options.logo = Some(Logo {
+                image: match &self.logo {
+                    Some(path) => Some(open(path)?.to_rgba()),
+                    None => None,
+                },
+                size: self.logo_size.unwrap_or(0.2),
+            });
+        }
+
+        if let Some(path) = &self.halftone {
+            options.halftone = Some(open(path)?.to_luma());
+        }
+
+        Ok(options)
+    }
+}
+
+fn open(path: &str) -> Result<image::DynamicImage> {
+    image::open(path).map_err(|error| format!("couldn't open {}: {}", path, error).into())
+}
+
+// The data to encode, from the argument, a file or stdin, kept as bytes so it doesn't have to be text
+// Nothing is trimmed, so piped text keeps its trailing newline
+fn read_input(input: Option<String>, file: Option<String>) -> Result<Vec<u8>> {
+    match (input, file) {
+        (_, Some(path)) => fs::read(&path).map_err(|error| format!("couldn't read {}: {}", path, error).into()),
+        (Some(text), None) if text != "-" => Ok(text.into_bytes()),
+        _ => {
+            let mut stdin = io::stdin().lock();
+
+            if stdin.is_terminal() {
+                return Err("nothing to encode, give some text, a --file or pipe the data in".into());
+            }
+
+            let mut data = vec![];
+            stdin.read_to_end(&mut data)?;
+            Ok(data)
+        }
+    }
+}
+
+// Makes the code out of the segments and writes it wherever it's meant to go
+fn encode(segments: Vec<Segment>, required: Option<EcLevel>, args: &CodeArgs) -> Result<()> {
+    let ec_level = args.ec_level(required)?;
+    let options = args.render_options()?;
+
+    let code = render::fit(
+        segments,
+        ec_level,
+        args.version.map(usize::from),
+        args.mask.map(usize::from),
+        args.bump_ec,
+        &options,
+    )?;
+
+    for warning in options.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    match (&args.output, args.format) {
+        (Some(path), None) => code.save_image(path, &options)?,
+        (Some(path), Some(format)) => render::write(&code, BufWriter::new(File::create(path)?), format, &options)?,
+        // The JSON takes the place of the code on stdout
+        (None, _) if args.json => (),
+        (None, format) => {
+            let format = format.unwrap_or(Format::Terminal);
+            check_stdout(format)?;
+            render::write(&code, io::stdout().lock(), format, &options)?
+        }
+    }
+
+    if args.json {
+        print_json(&code.metadata(args.matrix))?;
+    }
+
+    Ok(())
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    writeln!(io::stdout().lock(), "{}", json)?;
+    Ok(())
+}
+
+// Images written to a terminal just fill it with garbage
+fn check_stdout(format: Format) -> Result<()> {
+    if format.is_raster() && io::stdout().is_terminal() {
+        return Err("images can't be printed to the terminal, give an --output or redirect it".into());
+    }
+
+    Ok(())
+}
+
+// The input is split across three codes, one in each color channel, which only make sense as an image
+fn encode_rgb(input: &str, args: &CodeArgs) -> Result<()> {
+    let codes = rgb::encode(rgb::split(input), args.ec_level(None)?)?;
+    let options = args.render_options()?;
+
+    match (&args.output, args.format) {
+        (_, Some(format)) if !format.is_raster() => return Err("RGB codes can only be saved as images".into()),
+        (None, _) if args.json => (),
+        (Some(path), None) => rgb::save(&codes, path, &options)?,
+        (Some(path), Some(format)) => {
+            render::write_raster(&rgb::render(&codes, &options)?, BufWriter::new(File::create(path)?), format, options.dpi)?
+        }
+        (None, format) => {
+            let format = format.unwrap_or(Format::Png);
+            check_stdout(format)?;
+            render::write_raster(&rgb::render(&codes, &options)?, io::stdout().lock(), format, options.dpi)?
+        }
+    }
+
+    if args.json {
+        let metadata: Vec<_> = codes.iter().map(|code| code.metadata(args.matrix)).collect();
+        print_json(&metadata)?;
+    }
+
+    Ok(())
+}
+
+// Makes a code out of a payload, in its own EC level if it has one
+fn encode_payload(payload: &dyn Payload, args: &CodeArgs) -> Result<()> {
+    encode(payload.segments()?, payload.required_ec_level(), args)
+}
+
+// Makes every code in the file, reporting the rows that failed once the rest are done
+fn batch(file: &str, out_dir: &str, mode: ModeArg, jobs: Option<usize>, report: Option<String>, args: &CodeArgs) -> Result<()> {
+    if args.output.is_some() {
+        return Err("every row names its own output, use --out-dir to pick where they go".into());
+    }
+
+    if args.json {
+        return Err("batches don't print JSON, but the --report has every code's metadata".into());
+    }
+
+    let rows = batch::read(Path::new(file)).map_err(|error| format!("couldn't read {}: {}", file, error))?;
+
+    let settings = batch::Settings {
+        ec_level: args.ec_level(None)?,
+        mode: mode.mode(),
+        version: args.version.map(usize::from),
+        mask: args.mask.map(usize::from),
+        bump_ec: args.bump_ec,
+        format: args.format,
+        options: args.render_options()?,
+    };
+
+    for warning in settings.options.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    // Zero threads leaves it to rayon, which uses one per CPU
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs.unwrap_or(0)).build()?;
+    let outcomes = pool.install(|| batch::run(&rows, &settings, Path::new(out_dir)));
+
+    if let Some(path) = report {
+        let mut output = BufWriter::new(File::create(&path)?);
+
+        for outcome in outcomes.iter() {
+            writeln!(output, "{}", serde_json::to_string(outcome)?)?;
+        }
+    }
+
+    let failures: Vec<_> = outcomes.iter().filter(|outcome| outcome.error.is_some()).collect();
+
+    for failure in failures.iter() {
+        eprintln!(
+            "Line {} ({}): {}",
+            failure.line,
+            failure.output.as_deref().unwrap_or("unreadable row"),
+            failure.error.as_deref().unwrap_or_default()
+        );
+    }
+
+    eprintln!("Made {} of {} codes", outcomes.len() - failures.len(), outcomes.len());
+
+    match failures.len() {
+        0 => Ok(()),
+        1 => Err("1 row failed".into()),
+        count => Err(format!("{} rows failed", count).into()),
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Encode { input, file, mode, rgb, code } => {
+            let data = read_input(input, file)?;
+
+            if rgb {
+                let input = String::from_utf8(data).map_err(|_| "RGB codes can only hold text")?;
+                return encode_rgb(&input, &code);
+            }
+
+            let segment = match mode.mode() {
+                Some(mode) => Segment::with_mode(mode, &data)?,
+                None => Segment::auto(&data),
+            };
+
+            encode(vec![segment], None, &code)
+        }
+        Command::Batch { file, out_dir, mode, jobs, report, code } => batch(&file, &out_dir, mode, jobs, report, &code),
+        Command::Serve { address, threads, max_data, max_size, max_age } => {
+            let defaults = serve::Config::default();
+
+            let config = serve::Config {
+                max_data: max_data.unwrap_or(defaults.max_data),
+                max_size,
+                max_age,
+                threads,
+            };
+
+            let server = serve::Server::bind(&address, config).map_err(|error| error.to_string())?;
+
+            if let Some(address) = server.address() {
+                eprintln!("Serving codes at http://{}/qr", address);
+            }
+
+            server.run();
+            Ok(())
+        }
+        Command::Decode { image, rgb } => {
+            let data = if rgb {
+                rgb::decode(&open(&image)?.to_rgba())?.concat()
+            } else {
+                decode::decode_image(&open(&image)?.to_luma())?
+            };
+
+            // Data can be binary, so it's only given a trailing newline when a person is reading it
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&data)?;
+
+            if stdout.is_terminal() {
+                writeln!(stdout)?;
+            }
+
+            Ok(())
+        }
+        Command::Inspect { image, json } => {
+            let decoded = decode::read_image(&open(&image)?.to_luma())?;
+
+            if json {
+                return print_json(&decoded.metadata()?);
+            }
+
+            let size = decoded.version * 4 + 17;
+
+            let mut stdout = io::stdout().lock();
+
+            writeln!(stdout, "Version: {} ({}×{})", decoded.version, size, size)?;
+            writeln!(stdout, "EC level: {:?}", decoded.ec_level)?;
+            writeln!(stdout, "Mask: {}", decoded.mask)?;
+            writeln!(stdout, "Corrected codewords: {}", decoded.corrected)?;
+            writeln!(stdout, "Segments:")?;
+
+            for segment in decoded.segments.iter() {
+                writeln!(stdout, "  {:?}, {} characters", segment.mode, segment.data.len())?;
+            }
+
+            writeln!(stdout, "Data: {}", String::from_utf8_lossy(&decoded.data()))?;
+            Ok(())
+        }
+        Command::Wifi { ssid, password, security, hidden, eap, phase2, identity, anonymous_identity, code } => {
+            let wifi = Wifi {
+                ssid,
+                password,
+                security,
+                hidden,
+                eap: eap.map(|method| Eap {
+                    method,
+                    phase2,
+                    identity,
+                    anonymous_identity,
+                }),
+            };
+
+            encode_payload(&wifi, &code)
+        }
+        Command::Epc { name, iban, bic, amount, purpose, reference, text, information, code } => {
+            let epc = Epc {
+                bic,
+                name,
+                iban,
+                amount,
+                purpose,
+                remittance: reference.map(Remittance::Reference).or_else(|| text.map(Remittance::Text)),
+                information,
+            };
+
+            encode_payload(&epc, &code)
+        }
+        Command::Event { summary, start, end, tz, location, description, code } => {
+            // Times without an offset are taken to be in the --tz time zone if there is one
+            let zoned = |time: Time| match &tz {
+                Some(zone) => time.in_zone(zone),
+                None => time,
+            };
+
+            let event = Event {
+                summary,
+                start: zoned(start),
+                end: end.map(zoned),
+                location,
+                description,
+            };
+
+            encode_payload(&event, &code)
+        }
+        Command::Geo { lat, lon, altitude, query, code } => {
+            let geo = Geo {
+                latitude: lat,
+                longitude: lon,
+                altitude,
+                query,
+            };
+
+            encode_payload(&geo, &code)
+        }
+        Command::Sms { number, message, code } => encode_payload(&Sms { number, message }, &code),
+        Command::Tel { number, code } => encode_payload(&Tel { number }, &code),
+        Command::Mailto { to, cc, bcc, subject, body, code } => {
+            let trim = |list: Vec<String>| list.iter().map(|address| address.trim().to_string()).collect();
+
+            let mailto = Mailto {
+                to: trim(to),
+                cc: trim(cc),
+                bcc: trim(bcc),
+                subject,
+                body,
+            };
+
+            encode_payload(&mailto, &code)
+        }
+        Command::Otp { account, issuer, secret, counter, period, algorithm, digits, code } => {
+            // Without a secret, a new one is made and printed so it can be stored on the server
+            let secret = match secret {
+                Some(secret) => otp::base32_decode(&secret)?,
+                None => {
+                    let secret = otp::generate_secret()?;
+                    eprintln!("Secret: {}", otp::base32_encode(&secret));
+                    secret
+                }
+            };
+
+            let otp = Otp {
+                kind: match counter {
+                    Some(counter) => Kind::Hotp { counter },
+                    None => Kind::Totp { period },
+                },
+                issuer,
+                account,
+                secret,
+                algorithm,
+                digits,
+            };
+
+            encode_payload(&otp, &code)
+        }
+    }
+}
+
+fn is_broken_pipe(error: &(dyn std::error::Error + 'static)) -> bool {
+    let io = match error.downcast_ref::<image::ImageError>() {
+        Some(image::ImageError::IoError(error)) => Some(error),
+        _ => error.downcast_ref::<io::Error>(),
+    };
+
+    io.is_some_and(|error| error.kind() == io::ErrorKind::BrokenPipe)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(()) => ExitCode::SUCCESS,
+        // Whatever was reading the output stopped early, like head does, which isn't worth complaining about
+        Err(error) if is_broken_pipe(error.as_ref()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::FAILURE
+        }
     }
 }
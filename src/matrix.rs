@@ -0,0 +1,74 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+// The modules of a code, indexed (x, y) like everything else, with the colours and which ones belong to function
+// patterns kept as separate bitmaps packed 64 to a word
+// Function modules are the finders, separators, timing and alignment patterns, version and format information and
+// the dark module, which are never masked
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Matrix {
+    size: usize,
+    dark: Vec<u64>,
+    function: Vec<u64>,
+}
+
+impl Matrix {
+    // A matrix of light data modules
+    pub fn new(size: usize) -> Matrix {
+        let words = (size * size).div_ceil(64);
+
+        Matrix {
+            size,
+            dark: vec![0; words],
+            function: vec![0; words],
+        }
+    }
+
+    // The word and bit a module is kept in
+    fn locate(&self, x: usize, y: usize) -> (usize, u64) {
+        assert!(x < self.size && y < self.size, "({}, {}) is outside the matrix", x, y);
+        let index = y * self.size + x;
+        (index / 64, 1 << (index % 64))
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        let (word, bit) = self.locate(x, y);
+        self.dark[word] & bit != 0
+    }
+
+    pub fn is_function(&self, x: usize, y: usize) -> bool {
+        let (word, bit) = self.locate(x, y);
+        self.function[word] & bit != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, dark: bool) {
+        let (word, bit) = self.locate(x, y);
+
+        if dark {
+            self.dark[word] |= bit;
+        } else {
+            self.dark[word] &= !bit;
+        }
+    }
+
+    // Sets a module and marks it as part of a function pattern, so data and masks leave it alone
+    pub fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        let (word, bit) = self.locate(x, y);
+        self.function[word] |= bit;
+        self.set(x, y, dark);
+    }
+
+    // Flips a module, unless it's part of a function pattern
+    pub fn flip(&mut self, x: usize, y: usize) {
+        let (word, bit) = self.locate(x, y);
+
+        if self.function[word] & bit == 0 {
+            self.dark[word] ^= bit;
+        }
+    }
+
+    // Number of dark modules, counted a word at a time
+    pub fn dark_count(&self) -> usize {
+        self.dark.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
@@ -0,0 +1,229 @@
+pub mod contact;
+pub mod crypto;
+pub mod emv;
+pub mod epc;
+pub mod event;
+pub mod geo;
+pub mod message;
+pub mod otp;
+pub mod swiss;
+pub mod wifi;
+
+use crate::error::Error;
+use crate::qr::{EcLevel, QR};
+use crate::segment::Segment;
+
+// Structured data that apps know how to act on when they scan it, like joining a network or saving a contact
+
+pub trait Payload {
+    // The text that goes in the code
+    fn encode(&self) -> Result<String, Error>;
+
+    // The payload split up into segments, so parts of it can use denser modes than bytes
+    fn segments(&self) -> Result<Vec<Segment>, Error> {
+        Ok(vec![Segment::bytes(self.encode()?.as_bytes())])
+    }
+
+    // EC level the format insists on, if it has one
+    fn required_ec_level(&self) -> Option<EcLevel> {
+        None
+    }
+}
+
+// Generates a code holding the payload, using the format's own EC level in place of the given one if it has one
+pub fn generate(payload: &dyn Payload, ec_level: EcLevel) -> Result<QR, Error> {
+    let mut code = QR::with_segments(payload.segments()?, payload.required_ec_level().unwrap_or(ec_level))?;
+    code.generate();
+    Ok(code)
+}
+
+// Backslash-escapes every character in special, plus the backslash itself
+// Used by the formats built from FIELD:value; pairs, where the separators can't show up in values as-is
+pub(crate) fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        if character == '\\' || special.contains(character) {
+            escaped.push('\\');
+        }
+
+        escaped.push(character);
+    }
+
+    escaped
+}
+
+// Lines of vCards and iCalendar events longer than this many bytes have to be folded onto the next line
+const FOLD_LENGTH: usize = 75;
+
+// Escapes a vCard or iCalendar text value, which also needs newlines written out as \n
+pub(crate) fn escape_text(value: &str) -> String {
+    escape(value, ";,").replace("\r\n", "\\n").replace('\n', "\\n")
+}
+
+// Breaks a vCard or iCalendar line up so no piece is longer than FOLD_LENGTH bytes, never splitting a character
+// Each continuation line starts with a space, which takes up one of its bytes
+pub(crate) fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut length = 0;
+
+    for character in line.chars() {
+        if length + character.len_utf8() > FOLD_LENGTH {
+            folded += "\r\n ";
+            length = 1;
+        }
+
+        folded.push(character);
+        length += character.len_utf8();
+    }
+
+    folded + "\r\n"
+}
+
+// Percent-encodes everything but unreserved characters and the ones in keep, for putting values in URIs
+pub(crate) fn percent_encode(value: &str, keep: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        let character = byte as char;
+
+        if byte.is_ascii_alphanumeric() || "-._~".contains(character) || (byte.is_ascii() && keep.contains(character)) {
+            encoded.push(character);
+        } else {
+            encoded += &format!("%{:02X}", byte);
+        }
+    }
+
+    encoded
+}
+
+// Parses an amount like 12.5 or 1234.56 into cents, so nothing is lost to floating point
+pub fn parse_amount(amount: &str) -> Result<u64, Error> {
+    let invalid = || Error::InvalidField {
+        field: "amount",
+        reason: format!("{} isn't an amount with at most 2 decimal places", amount),
+    };
+
+    let (whole, fraction) = match amount.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (amount, ""),
+    };
+
+    let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+
+    if whole.is_empty() || fraction.len() > 2 || !digits(whole) || !digits(fraction) {
+        return Err(invalid());
+    }
+
+    let whole: u64 = whole.parse().map_err(|_| invalid())?;
+    let fraction: u64 = format!("{:0<2}", fraction).parse().map_err(|_| invalid())?;
+
+    whole.checked_mul(100).and_then(|cents| cents.checked_add(fraction)).ok_or_else(invalid)
+}
+
+// Writes cents back out with exactly 2 decimal places
+pub(crate) fn format_amount(cents: u64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+// Moves the first 4 characters to the end, counts letters as 10 to 35 and takes the whole thing mod 97
+// IBANs and RF creditor references are both valid when this comes out as 1
+pub(crate) fn mod97(text: &str) -> u32 {
+    let bytes = text.as_bytes();
+    let mut remainder = 0;
+
+    for &byte in bytes[4..].iter().chain(bytes[..4].iter()) {
+        let value = (byte as char).to_digit(36).unwrap_or(0);
+        let scale = if value < 10 { 10 } else { 100 };
+        remainder = (remainder * scale + value) % 97;
+    }
+
+    remainder
+}
+
+// Checks an IBAN's length, country code and mod 97 check digits, returning it without spaces and in uppercase
+pub fn validate_iban(iban: &str) -> Result<String, Error> {
+    let iban: String = iban.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+    let invalid = |reason: &str| Error::InvalidField {
+        field: "IBAN",
+        reason: reason.to_string(),
+    };
+
+    if iban.len() < 15 || iban.len() > 34 {
+        return Err(invalid("IBANs are 15 to 34 characters"));
+    }
+
+    let bytes = iban.as_bytes();
+
+    if !bytes[..2].iter().all(u8::is_ascii_uppercase)
+        || !bytes[2..4].iter().all(u8::is_ascii_digit)
+        || !bytes.iter().all(u8::is_ascii_alphanumeric)
+    {
+        return Err(invalid("IBANs are a country code and 2 check digits followed by letters and digits"));
+    }
+
+    if mod97(&iban) != 1 {
+        return Err(invalid("check digits don't match"));
+    }
+
+    Ok(iban)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape_text(r"a,b;c\d"), r"a\,b\;c\\d");
+        assert_eq!(escape_text("one\ntwo\r\nthree"), "one\\ntwo\\nthree");
+        assert_eq!(escape("a:b\"c", ":\""), "a\\:b\\\"c");
+    }
+
+    #[test]
+    fn folds_at_75_octets() {
+        let line = "x".repeat(75);
+        assert_eq!(fold(&line), format!("{}\r\n", line));
+
+        let line = "x".repeat(76);
+        assert_eq!(fold(&line), format!("{}\r\n x\r\n", "x".repeat(75)));
+
+        // Every piece, with its leading space, stays within 75 octets, and the é that doesn't fit moves down whole
+        let line = format!("{}é{}", "x".repeat(74), "y".repeat(200));
+        let folded = fold(&line);
+        let pieces: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+
+        assert_eq!(pieces[0], "x".repeat(74));
+        assert!(pieces[1].starts_with(" é"));
+        assert!(pieces.iter().all(|piece| piece.len() <= FOLD_LENGTH));
+        assert!(pieces[1..].iter().all(|piece| piece.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn reads_and_writes_amounts() {
+        assert_eq!(parse_amount("12.5"), Ok(1250));
+        assert_eq!(parse_amount("0.01"), Ok(1));
+        assert_eq!(parse_amount("999999999.99"), Ok(99_999_999_999));
+        assert_eq!(format_amount(99_999_999_999), "999999999.99");
+        assert_eq!(format_amount(5), "0.05");
+
+        for bad in ["", ".5", "1.234", "-1", "1,00", "1e3", "184467440737095516.16"] {
+            assert!(parse_amount(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn checks_ibans() {
+        // The examples from the EPC's quick reference guide and the ECBS IBAN standard
+        assert_eq!(validate_iban("BE71 0961 2345 6769"), Ok("BE71096123456769".to_string()));
+        assert_eq!(validate_iban("de89 3704 0044 0532 0130 00"), Ok("DE89370400440532013000".to_string()));
+        assert_eq!(validate_iban("GB29NWBK60161331926819"), Ok("GB29NWBK60161331926819".to_string()));
+
+        assert!(validate_iban("BE71 0961 2345 6768").is_err());
+        assert!(validate_iban("GB29NWBK6016133192681").is_err());
+        assert!(validate_iban("1229NWBK60161331926819").is_err());
+        assert!(validate_iban("GB29NWBK6016133192681!").is_err());
+        assert!(validate_iban("BE71").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContactFormat {
+    VCard3,
+    VCard4,
+    // Docomo's MeCard, which is much denser but can't carry organisations or phone types
+    MeCard,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Phone {
+    pub number: String,
+    // What sort of number it is, like cell, work or home
+    pub kind: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Address {
+    pub street: String,
+    pub city: String,
+    pub region: String,
+    pub postal_code: String,
+    pub country: String,
+}
+
+// A contact card, encoded as whichever of vCard or MeCard comes out smallest unless a format is picked
+#[derive(Clone, Debug, Default)]
+pub struct Contact {
+    pub given_name: String,
+    pub family_name: String,
+    pub phones: Vec<Phone>,
+    pub emails: Vec<String>,
+    pub organization: Option<String>,
+    pub address: Option<Address>,
+    pub url: Option<String>,
+    pub format: Option<ContactFormat>,
+}
+
+impl Contact {
+    // Name to display, given name first
+    fn full_name(&self) -> String {
+        [&self.given_name, &self.family_name]
+            .iter()
+            .filter(|name| !name.is_empty())
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Whether the format has somewhere to put every field that's been filled in
+    pub fn fits(&self, format: ContactFormat) -> bool {
+        format != ContactFormat::MeCard
+            || (self.organization.is_none() && self.phones.iter().all(|phone| phone.kind.is_none()))
+    }
+
+    // Encodes the contact in a specific format
+    pub fn encode_as(&self, format: ContactFormat) -> Result<String, Error> {
+        if self.given_name.is_empty() && self.family_name.is_empty() {
+            return Err(Error::MissingField("name"));
+        }
+
+        if !self.fits(format) {
+            return Err(Error::InvalidField {
+                field: "format",
+                reason: "MeCard can't hold organisations or phone types".to_string(),
+            });
+        }
+
+        Ok(match format {
+            ContactFormat::MeCard => self.mecard(),
+            _ => self.vcard(format),
+        })
+    }
+
+    fn vcard(&self, format: ContactFormat) -> String {
+        let version = if format == ContactFormat::VCard3 { "3.0" } else { "4.0" };
+        let mut lines = vec!["BEGIN:VCARD".to_string(), format!("VERSION:{}", version)];
+
+        lines.push(format!(
+            "N:{};{};;;",
+            payload::escape_text(&self.family_name),
+            payload::escape_text(&self.given_name)
+        ));
+        lines.push(format!("FN:{}", payload::escape_text(&self.full_name())));
+
+        if let Some(organization) = &self.organization {
+            lines.push(format!("ORG:{}", payload::escape_text(organization)));
+        }
+
+        for phone in self.phones.iter() {
+            // 3.0 shouts its types, 4.0 writes them in lowercase
+            let kind = match (&phone.kind, format) {
+                (Some(kind), ContactFormat::VCard3) => format!(";TYPE={}", kind.to_uppercase()),
+                (Some(kind), _) => format!(";TYPE={}", kind.to_lowercase()),
+                (None, _) => String::new(),
+            };
+
+            lines.push(format!("TEL{}:{}", kind, payload::escape_text(&phone.number)));
+        }
+
+        for email in self.emails.iter() {
+            lines.push(format!("EMAIL:{}", payload::escape_text(email)));
+        }
+
+        if let Some(address) = &self.address {
+            // The PO box and extended address come first, but nobody uses them
+            lines.push(format!(
+                "ADR:;;{};{};{};{};{}",
+                payload::escape_text(&address.street),
+                payload::escape_text(&address.city),
+                payload::escape_text(&address.region),
+                payload::escape_text(&address.postal_code),
+                payload::escape_text(&address.country)
+            ));
+        }
+
+        if let Some(url) = &self.url {
+            // URLs are URIs rather than text, so they're written as they are
+            lines.push(format!("URL:{}", url));
+        }
+
+        lines.push("END:VCARD".to_string());
+        lines.iter().map(|line| payload::fold(line)).collect()
+    }
+
+    fn mecard(&self) -> String {
+        // MeCard has no way of writing a newline, so lines are joined with spaces
+        let escape = |value: &str| payload::escape(&value.replace("\r\n", " ").replace('\n', " "), ";,:\"");
+        let mut encoded = format!("MECARD:N:{},{};", escape(&self.family_name), escape(&self.given_name));
+
+        for phone in self.phones.iter() {
+            encoded += &format!("TEL:{};", escape(&phone.number));
+        }
+
+        for email in self.emails.iter() {
+            encoded += &format!("EMAIL:{};", escape(email));
+        }
+
+        if let Some(address) = &self.address {
+            // Same order as vCard, but split with commas
+            encoded += &format!(
+                "ADR:,,{},{},{},{},{};",
+                escape(&address.street),
+                escape(&address.city),
+                escape(&address.region),
+                escape(&address.postal_code),
+                escape(&address.country)
+            );
+        }
+
+        if let Some(url) = &self.url {
+            encoded += &format!("URL:{};", escape(url));
+        }
+
+        encoded + ";"
+    }
+}
+
+impl Payload for Contact {
+    fn encode(&self) -> Result<String, Error> {
+        if let Some(format) = self.format {
+            return self.encode_as(format);
+        }
+
+        let mut smallest: Option<String> = None;
+
+        for &format in [ContactFormat::MeCard, ContactFormat::VCard3, ContactFormat::VCard4].iter() {
+            if !self.fits(format) {
+                continue;
+            }
+
+            let encoded = self.encode_as(format)?;
+
+            if smallest.as_ref().is_none_or(|smallest| encoded.len() < smallest.len()) {
+                smallest = Some(encoded);
+            }
+        }
+
+        // vCard can hold everything, so there's always at least one
+        Ok(smallest.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact() -> Contact {
+        Contact {
+            given_name: "Ada".to_string(),
+            family_name: "Lovelace".to_string(),
+            phones: vec![Phone {
+                number: "+44 20 7946 0000".to_string(),
+                kind: None,
+            }],
+            emails: vec!["ada@example.com".to_string()],
+            ..Contact::default()
+        }
+    }
+
+    #[test]
+    fn writes_mecards() {
+        let contact = Contact {
+            given_name: "Ada; \"the\"\ncountess".to_string(),
+            url: Some("https://example.com/a,b:c".to_string()),
+            ..contact()
+        };
+
+        assert_eq!(
+            contact.encode_as(ContactFormat::MeCard).unwrap(),
+            concat!(
+                r#"MECARD:N:Lovelace,Ada\; \"the\" countess;TEL:+44 20 7946 0000;EMAIL:ada@example.com;"#,
+                r"URL:https\://example.com/a\,b\:c;;"
+            )
+        );
+    }
+
+    #[test]
+    fn writes_vcards() {
+        let contact = Contact {
+            family_name: "Lovelace, née Byron".to_string(),
+            organization: Some("Analytical Engine; Ltd\\".to_string()),
+            phones: vec![Phone {
+                number: "+44 20 7946 0000".to_string(),
+                kind: Some("Cell".to_string()),
+            }],
+            ..contact()
+        };
+
+        assert_eq!(
+            contact.encode_as(ContactFormat::VCard3).unwrap(),
+            concat!(
+                "BEGIN:VCARD\r\n",
+                "VERSION:3.0\r\n",
+                "N:Lovelace\\, née Byron;Ada;;;\r\n",
+                "FN:Ada Lovelace\\, née Byron\r\n",
+                "ORG:Analytical Engine\\; Ltd\\\\\r\n",
+                "TEL;TYPE=CELL:+44 20 7946 0000\r\n",
+                "EMAIL:ada@example.com\r\n",
+                "END:VCARD\r\n",
+            )
+        );
+        assert!(contact.encode_as(ContactFormat::VCard4).unwrap().contains("TEL;TYPE=cell:"));
+    }
+
+    #[test]
+    fn folds_long_vcard_lines() {
+        let contact = Contact {
+            address: Some(Address {
+                street: "Flat 12, Wollaton Court, 1 Long Street".to_string(),
+                city: "Nottingham".to_string(),
+                region: "Nottinghamshire".to_string(),
+                postal_code: "NG1 1AA".to_string(),
+                country: "United Kingdom".to_string(),
+            }),
+            ..contact()
+        };
+
+        let vcard = contact.encode_as(ContactFormat::VCard4).unwrap();
+        assert!(vcard.split("\r\n").all(|line| line.len() <= 75));
+        assert!(vcard.contains("\r\n "));
+        assert!(vcard.replace("\r\n ", "").contains(
+            "ADR:;;Flat 12\\, Wollaton Court\\, 1 Long Street;Nottingham;Nottinghamshire;NG1 1AA;United Kingdom\r\n"
+        ));
+    }
+
+    #[test]
+    fn picks_the_smallest_format() {
+        let simple = contact();
+        assert_eq!(simple.encode().unwrap(), simple.encode_as(ContactFormat::MeCard).unwrap());
+
+        // MeCard has nowhere to put an organisation, so the smaller vCard is used, which is 3.0 when they tie
+        let organized = Contact {
+            organization: Some("Analytical Engine".to_string()),
+            ..contact()
+        };
+        assert!(!organized.fits(ContactFormat::MeCard));
+        assert!(organized.encode_as(ContactFormat::MeCard).is_err());
+        assert_eq!(organized.encode().unwrap(), organized.encode_as(ContactFormat::VCard3).unwrap());
+
+        let picked = Contact {
+            format: Some(ContactFormat::VCard4),
+            ..contact()
+        };
+        assert!(picked.encode().unwrap().contains("VERSION:4.0"));
+
+        assert_eq!(Contact::default().encode(), Err(Error::MissingField("name")));
+    }
+}
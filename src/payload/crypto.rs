@@ -0,0 +1,468 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+use crate::segment::{Mode, Segment};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// What the bech32 checksum comes out as for segwit version 0 addresses, and bech32m for every later version
+const BECH32_CONSTANT: u32 = 1;
+const BECH32M_CONSTANT: u32 = 0x2bc8_30a3;
+
+// Satoshis in a bitcoin
+const SATOSHIS: u64 = 100_000_000;
+
+fn invalid_address(reason: &str) -> Error {
+    Error::InvalidField {
+        field: "address",
+        reason: reason.to_string(),
+    }
+}
+
+// Checks a legacy address, which is a version byte, a 20 byte hash and a 4 byte double SHA-256 checksum in base58
+fn validate_base58(address: &str) -> Result<(), Error> {
+    let mut bytes: Vec<u8> = vec![];
+
+    for character in address.chars() {
+        let mut carry = BASE58_ALPHABET
+            .find(character)
+            .ok_or_else(|| invalid_address("has a character that isn't base58"))?;
+
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as usize * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    // Each leading 1 stands for a leading zero byte
+    let zeroes = address.chars().take_while(|&c| c == '1').count();
+    let bytes = [vec![0; zeroes], bytes].concat();
+
+    if bytes.len() != 25 {
+        return Err(invalid_address("base58 addresses are 25 bytes"));
+    }
+
+    let checksum = Sha256::digest(Sha256::digest(&bytes[..21]));
+
+    if checksum[..4] != bytes[21..] {
+        return Err(invalid_address("checksum doesn't match"));
+    }
+
+    // Pay to public key hash and pay to script hash, on mainnet then testnet
+    if ![0x00, 0x05, 0x6f, 0xc4].contains(&bytes[0]) {
+        return Err(invalid_address("isn't a bitcoin address"));
+    }
+
+    Ok(())
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATORS: [u32; 5] = [0x3b6a_57b2, 0x2650_8e6d, 0x1ea1_19fa, 0x3d42_33dd, 0x2a14_62b3];
+    let mut checksum = 1;
+
+    for &value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ff_ffff) << 5) ^ value as u32;
+
+        for (i, generator) in GENERATORS.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+
+    checksum
+}
+
+// What the checksum of a human-readable part and its data comes out as, one of the two constants if it's right
+// The human-readable part goes in as the high bits of each character, a zero, then the low bits
+fn bech32_checksum(hrp: &str, data: &[u8]) -> u32 {
+    let mut values: Vec<u8> = hrp.bytes().map(|byte| byte >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|byte| byte & 31));
+    values.extend(data);
+    bech32_polymod(&values)
+}
+
+// Checks a segwit address, which is bech32 for version 0 and bech32m for later versions
+fn validate_bech32(address: &str) -> Result<(), Error> {
+    if address.len() > 90 || (address.to_lowercase() != address && address.to_uppercase() != address) {
+        return Err(invalid_address("bech32 addresses can't be mixed case or longer than 90 characters"));
+    }
+
+    let address = address.to_lowercase();
+    let (hrp, data) = address.rsplit_once('1').ok_or_else(|| invalid_address("has no separator"))?;
+
+    if !["bc", "tb", "bcrt"].contains(&hrp) || data.len() < 7 {
+        return Err(invalid_address("isn't a bitcoin address"));
+    }
+
+    let data: Vec<u8> = data
+        .chars()
+        .map(|character| BECH32_CHARSET.find(character).map(|value| value as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid_address("has a character that isn't bech32"))?;
+
+    let witness_version = data[0];
+    let constant = if witness_version == 0 { BECH32_CONSTANT } else { BECH32M_CONSTANT };
+
+    if bech32_checksum(hrp, &data) != constant {
+        return Err(invalid_address("checksum doesn't match"));
+    }
+
+    // The witness program is the rest of the data minus the checksum, regrouped from 5 bit to 8 bit values
+    let mut program = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for &value in &data[1..data.len() - 6] {
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            program.push((buffer >> bits) as u8);
+        }
+    }
+
+    let valid = witness_version <= 16
+        && bits < 5
+        && buffer & ((1 << bits) - 1) == 0
+        && (2..=40).contains(&program.len())
+        && (witness_version != 0 || program.len() == 20 || program.len() == 32);
+
+    if !valid {
+        return Err(invalid_address("witness program isn't valid"));
+    }
+
+    Ok(())
+}
+
+// Whether an address is a segwit address, which start with their network's human-readable part and a 1
+fn is_bech32(address: &str) -> bool {
+    let lowercase = address.to_lowercase();
+    ["bc1", "tb1", "bcrt1"].iter().any(|prefix| lowercase.starts_with(prefix))
+}
+
+// A bitcoin payment request, as a BIP21 bitcoin: URI
+#[derive(Clone, Debug, Default)]
+pub struct Bitcoin {
+    // Legacy base58 or segwit bech32 address, where uppercase bech32 addresses make smaller codes
+    pub address: String,
+    // Amount in satoshis
+    pub amount: Option<u64>,
+    // Name of who's being paid
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+impl Bitcoin {
+    // Whether the scheme and address can go in an alphanumeric segment, which only uppercase bech32 addresses can
+    fn alphanumeric(&self) -> bool {
+        is_bech32(&self.address) && self.address.to_uppercase() == self.address
+    }
+
+    // The URI split into the scheme and address, then the parameters
+    fn parts(&self) -> Result<(String, String), Error> {
+        if is_bech32(&self.address) {
+            validate_bech32(&self.address)?;
+        } else {
+            validate_base58(&self.address)?;
+        }
+
+        // The scheme isn't case sensitive, so it's uppercase along with the address so they can share a segment
+        let scheme = if self.alphanumeric() { "BITCOIN" } else { "bitcoin" };
+        let mut parameters = vec![];
+
+        if let Some(amount) = self.amount {
+            // Amounts are in bitcoin, without any trailing zeroes
+            let fraction = format!("{:08}", amount % SATOSHIS);
+            let fraction = fraction.trim_end_matches('0');
+
+            if fraction.is_empty() {
+                parameters.push(format!("amount={}", amount / SATOSHIS));
+            } else {
+                parameters.push(format!("amount={}.{}", amount / SATOSHIS, fraction));
+            }
+        }
+
+        if let Some(label) = &self.label {
+            parameters.push(format!("label={}", payload::percent_encode(label, "")));
+        }
+
+        if let Some(message) = &self.message {
+            parameters.push(format!("message={}", payload::percent_encode(message, "")));
+        }
+
+        let query = if parameters.is_empty() { String::new() } else { format!("?{}", parameters.join("&")) };
+
+        Ok((format!("{}:{}", scheme, self.address), query))
+    }
+}
+
+impl Payload for Bitcoin {
+    fn encode(&self) -> Result<String, Error> {
+        let (address, query) = self.parts()?;
+        Ok(address + &query)
+    }
+
+    fn segments(&self) -> Result<Vec<Segment>, Error> {
+        let (address, query) = self.parts()?;
+
+        if !self.alphanumeric() {
+            return Ok(vec![Segment::bytes((address + &query).as_bytes())]);
+        }
+
+        let mut segments = vec![Segment::with_mode(Mode::Alphanumeric, address.as_bytes())?];
+
+        if !query.is_empty() {
+            segments.push(Segment::bytes(query.as_bytes()));
+        }
+
+        Ok(segments)
+    }
+}
+
+// Checks an Ethereum address is 20 bytes of hex, and if it's mixed case, that the case matches its EIP-55 checksum
+pub fn validate_ethereum_address(address: &str) -> Result<(), Error> {
+    let hex = match address.strip_prefix("0x") {
+        Some(hex) if hex.len() == 40 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()) => hex,
+        _ => return Err(invalid_address("Ethereum addresses are 0x followed by 40 hex digits")),
+    };
+
+    let has_upper = hex.bytes().any(|byte| byte.is_ascii_uppercase());
+    let has_lower = hex.bytes().any(|byte| byte.is_ascii_lowercase());
+
+    if has_upper && has_lower {
+        // Each letter is uppercase when the matching hex digit of the hash of the lowercase address is 8 or more
+        let hash = Keccak256::digest(hex.to_lowercase().as_bytes());
+
+        let matches = hex.bytes().enumerate().all(|(i, byte)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0xF;
+            !byte.is_ascii_alphabetic() || byte.is_ascii_uppercase() == (nibble >= 8)
+        });
+
+        if !matches {
+            return Err(invalid_address("checksum doesn't match"));
+        }
+    }
+
+    Ok(())
+}
+
+// Function names and parameter names have to be plain identifiers
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.')
+}
+
+// An Ethereum payment or contract call, as an EIP-681 ethereum: URI
+#[derive(Clone, Debug, Default)]
+pub struct Ethereum {
+    // Who's being paid, or the contract being called
+    pub address: String,
+    // Network to use, where 1 is mainnet, or None to use whichever the wallet is on
+    pub chain_id: Option<u64>,
+    // Amount of ether to send, in wei
+    pub value: Option<u128>,
+    // Contract function to call, like transfer
+    pub function: Option<String>,
+    // Arguments of the function, like address or uint256, in order
+    pub parameters: Vec<(String, String)>,
+}
+
+impl Ethereum {
+    // A transfer of an ERC-20 token, with the amount in the token's smallest unit
+    pub fn token_transfer(token: &str, to: &str, amount: u128) -> Ethereum {
+        Ethereum {
+            address: token.to_string(),
+            function: Some("transfer".to_string()),
+            parameters: vec![("address".to_string(), to.to_string()), ("uint256".to_string(), amount.to_string())],
+            ..Ethereum::default()
+        }
+    }
+}
+
+impl Payload for Ethereum {
+    fn encode(&self) -> Result<String, Error> {
+        validate_ethereum_address(&self.address)?;
+
+        let mut encoded = format!("ethereum:{}", self.address);
+
+        if let Some(chain_id) = self.chain_id {
+            encoded += &format!("@{}", chain_id);
+        }
+
+        if let Some(function) = &self.function {
+            if !is_identifier(function) {
+                return Err(Error::InvalidField {
+                    field: "function",
+                    reason: format!("{} isn't a function name", function),
+                });
+            }
+
+            encoded += &format!("/{}", function);
+        }
+
+        let mut parameters = vec![];
+
+        if let Some(value) = self.value {
+            parameters.push(format!("value={}", value));
+        }
+
+        for (name, value) in self.parameters.iter() {
+            if !is_identifier(name) {
+                return Err(Error::InvalidField {
+                    field: "parameter",
+                    reason: format!("{} isn't a parameter name", name),
+                });
+            }
+
+            // Address arguments get checked the same way as the address itself
+            if name == "address" {
+                validate_ethereum_address(value)?;
+            }
+
+            parameters.push(format!("{}={}", name, payload::percent_encode(value, "")));
+        }
+
+        if !parameters.is_empty() {
+            encoded += &format!("?{}", parameters.join("&"));
+        }
+
+        Ok(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The checksum constant a BIP-173 or BIP-350 test string comes out with, if it can be split up at all
+    fn checksum(text: &str) -> Option<u32> {
+        let text = text.to_lowercase();
+        let (hrp, data) = text.rsplit_once('1')?;
+        let data: Option<Vec<u8>> = data.chars().map(|c| BECH32_CHARSET.find(c).map(|value| value as u8)).collect();
+        Some(bech32_checksum(hrp, &data?))
+    }
+
+    #[test]
+    fn checksums_bip173_and_bip350_strings() {
+        let bech32 = [
+            "A12UEL5L",
+            "a12uel5l",
+            "an83characterlonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1tt5tgs",
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+            "11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqc8247j",
+            "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+            "?1ezyfcl",
+        ];
+
+        let bech32m = [
+            "A1LQFN3A",
+            "a1lqfn3a",
+            "an83characterlonghumanreadablepartthatcontainsthetheexcludedcharactersbioandnumber11sg7hg6",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "11llllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllludsr8",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa",
+        ];
+
+        for text in bech32.iter() {
+            assert_eq!(checksum(text), Some(BECH32_CONSTANT), "{}", text);
+        }
+
+        for text in bech32m.iter() {
+            assert_eq!(checksum(text), Some(BECH32M_CONSTANT), "{}", text);
+        }
+
+        // A changed character breaks either checksum
+        assert_ne!(checksum("a12uel5m"), Some(BECH32_CONSTANT));
+        assert_ne!(checksum("a1lqfn3q"), Some(BECH32M_CONSTANT));
+    }
+
+    #[test]
+    fn checks_segwit_addresses() {
+        let valid = [
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+            "BC1SW50QGDZ25J",
+            "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs",
+            "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ];
+
+        for address in valid.iter() {
+            assert_eq!(validate_bech32(address), Ok(()), "{}", address);
+        }
+
+        let invalid = [
+            // Unknown human-readable part
+            "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut",
+            // bech32 for a later version, and bech32m for version 0
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+            "tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf",
+            "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+            "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47",
+            // A character that isn't in the charset
+            "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+            // Version 17
+            "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R",
+            // Programs that are too short or too long, and one the wrong length for version 0
+            "bc1pw5dgrnzv",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+            "BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P",
+            // Mixed case
+            "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq",
+            // More than 4 bits of padding, and padding that isn't zero
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf",
+            "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j",
+            // No data at all
+            "bc1gmk9yu",
+        ];
+
+        for address in invalid.iter() {
+            assert!(validate_bech32(address).is_err(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn checks_base58_addresses() {
+        assert_eq!(validate_base58("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"), Ok(()));
+        assert_eq!(validate_base58("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"), Ok(()));
+
+        assert!(validate_base58("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb").is_err());
+        assert!(validate_base58("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfN").is_err());
+        assert!(validate_base58("1A1zP1eP5QGefi2DMPTfTL5SLmv7Divf0a").is_err());
+    }
+
+    #[test]
+    fn checks_eip55_addresses() {
+        let valid = [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+            // All one case doesn't carry a checksum
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed",
+            "0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED",
+        ];
+
+        for address in valid.iter() {
+            assert_eq!(validate_ethereum_address(address), Ok(()), "{}", address);
+        }
+
+        assert!(validate_ethereum_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(validate_ethereum_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA").is_err());
+        assert!(validate_ethereum_address("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+
+// EMVCo merchant-presented mode payloads, which many national instant payment schemes share
+// Everything is a list of fields, each a two digit tag, a two digit length and a value, where some values
+// are themselves lists of fields. Lengths count characters rather than bytes
+
+// Tag of the checksum, which always comes last
+const CRC_TAG: u8 = 63;
+
+// Globally unique identifier of the Pix scheme, which goes at the start of its merchant account template
+pub const PIX_GUI: &str = "br.gov.bcb.pix";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    Template(Vec<Field>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub tag: u8,
+    pub value: Value,
+}
+
+impl Field {
+    pub fn text(tag: u8, value: &str) -> Field {
+        Field {
+            tag,
+            value: Value::Text(value.to_string()),
+        }
+    }
+
+    pub fn template(tag: u8, fields: Vec<Field>) -> Field {
+        Field {
+            tag,
+            value: Value::Template(fields),
+        }
+    }
+
+    // The nested field with this tag, if this is a template that has one
+    pub fn get(&self, tag: u8) -> Option<&Field> {
+        match &self.value {
+            Value::Template(fields) => fields.iter().find(|field| field.tag == tag),
+            Value::Text(_) => None,
+        }
+    }
+}
+
+// Whether the value of a tag is a nested list of fields rather than text, given the template it's in if any
+// At the top these are the merchant account templates, additional data, language templates and the unreserved ones,
+// and the only templates nested inside those are the payment system specific ones from 50 in the additional data
+pub fn is_template(parent: Option<u8>, tag: u8) -> bool {
+    match parent {
+        None => matches!(tag, 26..=51 | 62 | 64 | 80..=99),
+        Some(62) => matches!(tag, 50..=99),
+        Some(_) => false,
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::InvalidField {
+        field: "EMV payload",
+        reason: reason.into(),
+    }
+}
+
+// Writes fields out as tag, length, value
+pub fn write(fields: &[Field]) -> Result<String, Error> {
+    let mut written = String::new();
+
+    for field in fields {
+        if field.tag > 99 {
+            return Err(invalid(format!("tag {} is more than 2 digits", field.tag)));
+        }
+
+        let value = match &field.value {
+            Value::Text(text) => text.clone(),
+            Value::Template(fields) => write(fields)?,
+        };
+
+        let length = value.chars().count();
+
+        if length == 0 || length > 99 {
+            return Err(invalid(format!("field {:02} has to be 1 to 99 characters", field.tag)));
+        }
+
+        written += &format!("{:02}{:02}{}", field.tag, length, value);
+    }
+
+    Ok(written)
+}
+
+// Reads fields out of a list, splitting templates into their own fields
+// parent is the tag of the template the list is the value of, or None for the whole payload
+fn read(characters: &[char], parent: Option<u8>) -> Result<Vec<Field>, Error> {
+    let mut fields = vec![];
+    let mut position = 0;
+
+    let number = |start: usize| -> Result<usize, Error> {
+        characters
+            .get(start..start + 2)
+            .and_then(|digits| digits.iter().collect::<String>().parse().ok())
+            .ok_or_else(|| invalid(format!("expected 2 digits at character {}", start)))
+    };
+
+    while position < characters.len() {
+        let tag = number(position)? as u8;
+        let length = number(position + 2)?;
+        let start = position + 4;
+
+        let value = characters
+            .get(start..start + length)
+            .ok_or_else(|| invalid(format!("field {:02} runs past the end", tag)))?;
+
+        fields.push(Field {
+            tag,
+            value: if is_template(parent, tag) {
+                Value::Template(read(value, Some(tag))?)
+            } else {
+                Value::Text(value.iter().collect())
+            },
+        });
+
+        position = start + length;
+    }
+
+    Ok(fields)
+}
+
+// CRC-16/CCITT-FALSE, which is what the checksum field holds
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+
+    crc
+}
+
+// Writes the fields followed by the checksum, which covers everything before it including its own tag and length
+pub fn write_with_crc(fields: &[Field]) -> Result<String, Error> {
+    let written = write(fields)? + &format!("{:02}04", CRC_TAG);
+    let crc = crc16(written.as_bytes());
+    Ok(format!("{}{:04X}", written, crc))
+}
+
+// Reads a whole payload, checking it starts with the format indicator and ends with a checksum that matches
+pub fn parse(payload: &str) -> Result<Vec<Field>, Error> {
+    let characters: Vec<char> = payload.chars().collect();
+    let mut fields = read(&characters, None)?;
+
+    if fields.first() != Some(&Field::text(0, "01")) {
+        return Err(invalid("doesn't start with payload format indicator 01"));
+    }
+
+    let crc = match fields.pop() {
+        Some(Field {
+            tag: CRC_TAG,
+            value: Value::Text(crc),
+        }) => crc,
+        _ => return Err(invalid("doesn't end with a checksum")),
+    };
+
+    // Everything but the 4 digits of the checksum itself
+    let covered = &payload[..payload.len() - crc.len()];
+
+    if u16::from_str_radix(&crc, 16).ok() != Some(crc16(covered.as_bytes())) {
+        return Err(invalid("checksum doesn't match"));
+    }
+
+    Ok(fields)
+}
+
+// Checks a text field is only digits, with a length in the range
+fn check_digits(field: &'static str, value: &str, lengths: std::ops::RangeInclusive<usize>) -> Result<(), Error> {
+    if !lengths.contains(&value.len()) || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(Error::InvalidField {
+            field,
+            reason: format!("has to be {} to {} digits", lengths.start(), lengths.end()),
+        });
+    }
+
+    Ok(())
+}
+
+// How the code is meant to be used, which the payer's app can use to stop the same payment going through twice
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Initiation {
+    // Printed and paid any number of times
+    Static,
+    // Made for a single payment
+    Dynamic,
+}
+
+// A merchant-presented payment code with the fields every scheme uses
+#[derive(Clone, Debug)]
+pub struct Merchant {
+    // Left out when None, which most schemes take to mean a static code
+    pub initiation: Option<Initiation>,
+    // Templates with tags from 26 to 51 saying which schemes the merchant takes payments through
+    pub accounts: Vec<Field>,
+    // Four digit ISO 18245 merchant category code, 0000 if there isn't one
+    pub category: String,
+    // Three digit ISO 4217 currency number, like 986 for the Brazilian real
+    pub currency: String,
+    // Amount in cents, or None to leave it for the payer to fill in
+    pub amount: Option<u64>,
+    // Two letter ISO 3166 country code
+    pub country: String,
+    pub name: String,
+    pub city: String,
+    pub postal_code: Option<String>,
+    // Fields of the additional data template, like a bill number or transaction ID
+    pub additional: Vec<Field>,
+}
+
+impl Default for Merchant {
+    fn default() -> Merchant {
+        Merchant {
+            initiation: None,
+            accounts: vec![],
+            category: "0000".to_string(),
+            currency: String::new(),
+            amount: None,
+            country: String::new(),
+            name: String::new(),
+            city: String::new(),
+            postal_code: None,
+            additional: vec![],
+        }
+    }
+}
+
+impl Merchant {
+    // A Pix code paying to a Pix key, like an email, phone number or random key
+    // Pix wants a transaction ID even on static codes, with *** meaning there isn't one
+    pub fn pix(key: &str, name: &str, city: &str, amount: Option<u64>, transaction_id: Option<&str>) -> Merchant {
+        Merchant {
+            accounts: vec![Field::template(26, vec![Field::text(0, PIX_GUI), Field::text(1, key)])],
+            currency: "986".to_string(),
+            amount,
+            country: "BR".to_string(),
+            name: name.to_string(),
+            city: city.to_string(),
+            additional: vec![Field::text(5, transaction_id.unwrap_or("***"))],
+            ..Merchant::default()
+        }
+    }
+
+    // A dynamic Pix code, where the payment details are fetched from the payment service provider's URL
+    pub fn pix_dynamic(url: &str, name: &str, city: &str) -> Merchant {
+        Merchant {
+            initiation: Some(Initiation::Dynamic),
+            accounts: vec![Field::template(26, vec![Field::text(0, PIX_GUI), Field::text(25, url)])],
+            additional: vec![Field::text(5, "***")],
+            ..Merchant::pix("", name, city, None, None)
+        }
+    }
+
+    pub fn fields(&self) -> Result<Vec<Field>, Error> {
+        if self.accounts.is_empty() {
+            return Err(Error::MissingField("merchant account"));
+        }
+
+        if let Some(account) = self.accounts.iter().find(|account| !(26..=51).contains(&account.tag)) {
+            return Err(Error::InvalidField {
+                field: "merchant account",
+                reason: format!("tag {} isn't from 26 to 51", account.tag),
+            });
+        }
+
+        check_digits("category", &self.category, 4..=4)?;
+        check_digits("currency", &self.currency, 3..=3)?;
+
+        if self.country.len() != 2 || !self.country.bytes().all(|byte| byte.is_ascii_uppercase()) {
+            return Err(Error::InvalidField {
+                field: "country",
+                reason: "has to be a 2 letter country code".to_string(),
+            });
+        }
+
+        if self.name.is_empty() {
+            return Err(Error::MissingField("name"));
+        }
+
+        if self.city.is_empty() {
+            return Err(Error::MissingField("city"));
+        }
+
+        let too_long = |field: &'static str, value: &str, max: usize| {
+            if value.chars().count() > max {
+                Err(Error::InvalidField {
+                    field,
+                    reason: format!("can be at most {} characters", max),
+                })
+            } else {
+                Ok(())
+            }
+        };
+
+        too_long("name", &self.name, 25)?;
+        too_long("city", &self.city, 15)?;
+
+        let mut fields = vec![Field::text(0, "01")];
+
+        match self.initiation {
+            Some(Initiation::Static) => fields.push(Field::text(1, "11")),
+            Some(Initiation::Dynamic) => fields.push(Field::text(1, "12")),
+            None => (),
+        }
+
+        fields.extend(self.accounts.iter().cloned());
+        fields.push(Field::text(52, &self.category));
+        fields.push(Field::text(53, &self.currency));
+
+        if let Some(amount) = self.amount {
+            let amount = payload::format_amount(amount);
+            too_long("amount", &amount, 13)?;
+            fields.push(Field::text(54, &amount));
+        }
+
+        fields.push(Field::text(58, &self.country));
+        fields.push(Field::text(59, &self.name));
+        fields.push(Field::text(60, &self.city));
+
+        if let Some(postal_code) = &self.postal_code {
+            too_long("postal code", postal_code, 10)?;
+            fields.push(Field::text(61, postal_code));
+        }
+
+        if !self.additional.is_empty() {
+            fields.push(Field::template(62, self.additional.clone()));
+        }
+
+        Ok(fields)
+    }
+}
+
+impl Payload for Merchant {
+    fn encode(&self) -> Result<String, Error> {
+        write_with_crc(&self.fields()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The static Pix example from the Banco Central do Brasil's Pix initiation manual
+    const PIX: &str = concat!(
+        "00020126580014br.gov.bcb.pix0136123e4567-e12b-12d1-a456-426655440000",
+        "5204000053039865802BR5913Fulano de Tal6008BRASILIA62070503***63041D3D"
+    );
+
+    #[test]
+    fn checksums_like_crc16_ccitt_false() {
+        // The check value of the catalogue of CRC algorithms
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(b""), 0xFFFF);
+        assert_eq!(crc16(&PIX.as_bytes()[..PIX.len() - 4]), 0x1D3D);
+    }
+
+    #[test]
+    fn writes_the_pix_example() {
+        let merchant = Merchant::pix("123e4567-e12b-12d1-a456-426655440000", "Fulano de Tal", "BRASILIA", None, None);
+        assert_eq!(merchant.encode().unwrap(), PIX);
+    }
+
+    #[test]
+    fn parses_templates_into_fields() {
+        let fields = parse(PIX).unwrap();
+
+        assert_eq!(fields.len(), 8);
+        assert_eq!(fields[1].get(0), Some(&Field::text(0, PIX_GUI)));
+        assert_eq!(fields[1].get(1), Some(&Field::text(1, "123e4567-e12b-12d1-a456-426655440000")));
+        assert_eq!(fields[7], Field::template(62, vec![Field::text(5, "***")]));
+
+        // Only the template tags are split up, so text that happens to look like fields stays text
+        assert_eq!(fields[5], Field::text(59, "Fulano de Tal"));
+        let nested = write_with_crc(&[Field::text(0, "01"), Field::text(59, "0002ab")]).unwrap();
+        assert_eq!(parse(&nested).unwrap()[1], Field::text(59, "0002ab"));
+        assert!([26, 51, 62, 64, 80].iter().all(|&tag| is_template(None, tag)));
+        assert!([25, 52, 63, 79].iter().all(|&tag| !is_template(None, tag)));
+    }
+
+    #[test]
+    fn only_nests_the_templates_each_template_has() {
+        assert!(is_template(Some(62), 50) && is_template(Some(62), 99));
+        assert!(!is_template(Some(62), 30) && !is_template(Some(26), 30) && !is_template(Some(26), 62));
+
+        // 30 is a template at the top, but only text inside a merchant account template
+        let account = Field::template(26, vec![Field::text(0, PIX_GUI), Field::text(30, "ab")]);
+        let payment_system = Field::template(50, vec![Field::text(0, "x")]);
+        let additional = Field::template(62, vec![Field::text(30, "0002ab"), payment_system]);
+        let fields = vec![Field::text(0, "01"), account, additional];
+
+        let written = write_with_crc(&fields).unwrap();
+        assert!(written.contains("3002ab"));
+        assert_eq!(parse(&written), Ok(fields));
+    }
+
+    #[test]
+    fn round_trips_fields() {
+        let merchant = Merchant {
+            initiation: Some(Initiation::Dynamic),
+            accounts: vec![Field::template(27, vec![Field::text(0, "com.example"), Field::text(3, "ação")])],
+            category: "5812".to_string(),
+            currency: "840".to_string(),
+            amount: Some(1050),
+            country: "US".to_string(),
+            name: "Café Example".to_string(),
+            city: "Springfield".to_string(),
+            postal_code: Some("12345".to_string()),
+            additional: vec![Field::text(1, "INV-1"), Field::template(50, vec![Field::text(0, "x")])],
+        };
+
+        let fields = merchant.fields().unwrap();
+        let written = write_with_crc(&fields).unwrap();
+        assert_eq!(parse(&written), Ok(fields));
+    }
+
+    #[test]
+    fn rejects_damaged_payloads() {
+        let tampered = PIX.replace("BRASILIA", "BRASILIO");
+        assert!(parse(&tampered).is_err());
+        assert!(parse(&PIX[..PIX.len() - 8]).is_err());
+        assert!(parse(&PIX[4..]).is_err());
+        assert!(parse("000201630400").is_err());
+        assert!(write(&[Field::text(1, "")]).is_err());
+        assert!(write(&[Field::text(100, "x")]).is_err());
+    }
+}
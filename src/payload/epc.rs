@@ -0,0 +1,237 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+use crate::qr::EcLevel;
+
+// Longest payload the EPC guidelines allow, in bytes
+pub const MAX_LENGTH: usize = 331;
+
+// Largest amount that can be asked for, in cents
+pub const MAX_AMOUNT: u64 = 99_999_999_999;
+
+// What the payer is told about the transfer, which can be one or the other but not both
+#[derive(Clone, Debug)]
+pub enum Remittance {
+    // Structured creditor reference, like an RF reference, up to 35 characters
+    Reference(String),
+    // Free text, up to 140 characters
+    Text(String),
+}
+
+// A SEPA credit transfer in the EPC069-12 format, known as a GiroCode in Germany and Austria
+// Always written as version 002, which makes the BIC optional inside the EEA, and in UTF-8
+#[derive(Clone, Debug, Default)]
+pub struct Epc {
+    pub bic: Option<String>,
+    pub name: String,
+    pub iban: String,
+    // Amount in euro cents, or None to leave it for the payer to fill in
+    pub amount: Option<u64>,
+    // Four letter ISO 20022 purpose code, like CHAR for charity
+    pub purpose: Option<String>,
+    pub remittance: Option<Remittance>,
+    // Note shown to the payer that isn't passed on with the transfer
+    pub information: Option<String>,
+}
+
+// Makes sure a field fits in its line and doesn't contain line breaks of its own
+fn check_length(field: &'static str, value: &str, max: usize) -> Result<(), Error> {
+    if value.chars().count() > max {
+        return Err(Error::InvalidField {
+            field,
+            reason: format!("can be at most {} characters", max),
+        });
+    }
+
+    if value.contains(['\r', '\n']) {
+        return Err(Error::InvalidField {
+            field,
+            reason: "can't contain line breaks".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+// Checks a BIC is a bank code, country code, location and optional branch
+fn validate_bic(bic: &str) -> Result<(), Error> {
+    let bytes = bic.as_bytes();
+    let valid = (bytes.len() == 8 || bytes.len() == 11)
+        && bytes[..6].iter().all(u8::is_ascii_uppercase)
+        && bytes[6..].iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit());
+
+    if !valid {
+        return Err(Error::InvalidField {
+            field: "BIC",
+            reason: "BICs are 8 or 11 capital letters and digits".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+impl Payload for Epc {
+    fn encode(&self) -> Result<String, Error> {
+        if self.name.is_empty() {
+            return Err(Error::MissingField("name"));
+        }
+
+        check_length("name", &self.name, 70)?;
+        let iban = payload::validate_iban(&self.iban)?;
+
+        if let Some(bic) = &self.bic {
+            validate_bic(bic)?;
+        }
+
+        let amount = match self.amount {
+            Some(amount) if amount == 0 || amount > MAX_AMOUNT => {
+                return Err(Error::InvalidField {
+                    field: "amount",
+                    reason: "has to be from 0.01 to 999999999.99".to_string(),
+                })
+            }
+            Some(amount) => format!("EUR{}", payload::format_amount(amount)),
+            None => String::new(),
+        };
+
+        if let Some(purpose) = &self.purpose {
+            if purpose.len() != 4 || !purpose.bytes().all(|byte| byte.is_ascii_uppercase()) {
+                return Err(Error::InvalidField {
+                    field: "purpose",
+                    reason: "purpose codes are 4 capital letters".to_string(),
+                });
+            }
+        }
+
+        let (reference, text) = match &self.remittance {
+            Some(Remittance::Reference(reference)) => {
+                check_length("reference", reference, 35)?;
+                (reference.as_str(), "")
+            }
+            Some(Remittance::Text(text)) => {
+                check_length("remittance text", text, 140)?;
+                ("", text.as_str())
+            }
+            None => ("", ""),
+        };
+
+        let information = self.information.as_deref().unwrap_or("");
+        check_length("information", information, 70)?;
+
+        let lines = [
+            "BCD",
+            "002",
+            // Character set 1 is UTF-8
+            "1",
+            "SCT",
+            self.bic.as_deref().unwrap_or(""),
+            &self.name,
+            &iban,
+            &amount,
+            self.purpose.as_deref().unwrap_or(""),
+            reference,
+            text,
+            information,
+        ];
+
+        // Empty lines at the end can be left off
+        let used = lines.iter().rposition(|line| !line.is_empty()).unwrap_or(0) + 1;
+        let encoded = lines[..used].join("\n");
+
+        if encoded.len() > MAX_LENGTH {
+            return Err(Error::PayloadTooLong {
+                length: encoded.len(),
+                max: MAX_LENGTH,
+            });
+        }
+
+        Ok(encoded)
+    }
+
+    fn required_ec_level(&self) -> Option<EcLevel> {
+        Some(EcLevel::M)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epc() -> Epc {
+        Epc {
+            bic: Some("COBADEFFXXX".to_string()),
+            name: "Deutsches Rotes Kreuz e.V.".to_string(),
+            iban: "DE89 3704 0044 0532 0130 00".to_string(),
+            amount: Some(1250),
+            purpose: Some("CHAR".to_string()),
+            ..Epc::default()
+        }
+    }
+
+    #[test]
+    fn writes_transfers() {
+        assert_eq!(
+            epc().encode().unwrap(),
+            "BCD\n002\n1\nSCT\nCOBADEFFXXX\nDeutsches Rotes Kreuz e.V.\nDE89370400440532013000\nEUR12.50\nCHAR"
+        );
+
+        let text = Epc {
+            bic: None,
+            amount: None,
+            purpose: None,
+            remittance: Some(Remittance::Text("Spende".to_string())),
+            ..epc()
+        };
+        assert_eq!(
+            text.encode().unwrap(),
+            "BCD\n002\n1\nSCT\n\nDeutsches Rotes Kreuz e.V.\nDE89370400440532013000\n\n\n\nSpende"
+        );
+    }
+
+    #[test]
+    fn checks_ibans_and_amounts() {
+        let bad_iban = Epc {
+            iban: "DE89 3704 0044 0532 0130 01".to_string(),
+            ..epc()
+        };
+        assert!(matches!(bad_iban.encode(), Err(Error::InvalidField { field: "IBAN", .. })));
+
+        let largest = Epc {
+            amount: Some(MAX_AMOUNT),
+            ..epc()
+        };
+        assert!(largest.encode().unwrap().contains("\nEUR999999999.99\n"));
+
+        for amount in [0, MAX_AMOUNT + 1] {
+            let epc = Epc {
+                amount: Some(amount),
+                ..epc()
+            };
+            assert!(matches!(epc.encode(), Err(Error::InvalidField { field: "amount", .. })));
+        }
+    }
+
+    #[test]
+    fn limits_payloads_to_331_bytes() {
+        let full = |information: &str, text: &str| Epc {
+            name: "n".repeat(70),
+            amount: Some(MAX_AMOUNT),
+            remittance: Some(Remittance::Text(text.to_string())),
+            information: Some(information.to_string()),
+            ..epc()
+        };
+
+        // Every line but the information is as long as it can be, which leaves 48 bytes for it
+        assert_eq!(full(&"i".repeat(48), &"t".repeat(140)).encode().unwrap().len(), MAX_LENGTH);
+        assert_eq!(
+            full(&"i".repeat(49), &"t".repeat(140)).encode(),
+            Err(Error::PayloadTooLong {
+                length: MAX_LENGTH + 1,
+                max: MAX_LENGTH
+            })
+        );
+
+        // The limit is in bytes, so 140 characters of text can go over it on their own
+        assert!(matches!(full("", &"é".repeat(140)).encode(), Err(Error::PayloadTooLong { .. })));
+        assert!(full("", &"t".repeat(141)).encode().is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct DateTime {
+    pub date: Date,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+// When an event starts or ends
+#[derive(Clone, Debug, PartialEq)]
+pub enum Time {
+    // A whole day, for all-day events
+    Date(Date),
+    // An exact moment, the same everywhere
+    Utc(DateTime),
+    // A wall clock time in an IANA time zone like Europe/Zurich, which calendars convert for themselves
+    Zoned { time: DateTime, zone: String },
+    // A wall clock time wherever the person looking at the event happens to be
+    Floating(DateTime),
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    pub fn is_valid(&self) -> bool {
+        (1..=9999).contains(&self.year)
+            && (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+    }
+
+    // Days since 1970-01-01, counting backwards for earlier dates
+    fn days(&self) -> i64 {
+        // Years start in March here, so the leap day falls at the end of the year
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    fn from_days(days: i64) -> Date {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+        let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+        let year = (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+
+        Date { year, month, day }
+    }
+}
+
+impl DateTime {
+    pub fn is_valid(&self) -> bool {
+        self.date.is_valid() && self.hour < 24 && self.minute < 60 && self.second < 60
+    }
+
+    // Moves the time by a number of seconds, rolling over into other days as needed
+    pub fn add_seconds(&self, seconds: i64) -> DateTime {
+        let total = self.date.days() * 86_400
+            + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+            + seconds;
+        let time = total.rem_euclid(86_400) as u32;
+
+        DateTime {
+            date: Date::from_days(total.div_euclid(86_400)),
+            hour: time / 3600,
+            minute: time / 60 % 60,
+            second: time % 60,
+        }
+    }
+}
+
+fn invalid_time(time: &str) -> Error {
+    Error::InvalidField {
+        field: "time",
+        reason: format!(
+            "{} isn't a date like 2024-05-01 or a time like 2024-05-01T09:30, 2024-05-01T09:30Z or 2024-05-01T09:30+02:00",
+            time
+        ),
+    }
+}
+
+// Parses a number out of part of the text, which has to be all digits
+fn digits(text: &str, range: std::ops::Range<usize>) -> Option<u32> {
+    let part = text.get(range)?;
+
+    if part.bytes().all(|byte| byte.is_ascii_digit()) {
+        part.parse().ok()
+    } else {
+        None
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Date, Error> {
+        let date = (|| {
+            if text.len() != 10 || text.get(4..5) != Some("-") || text.get(7..8) != Some("-") {
+                return None;
+            }
+
+            Some(Date {
+                year: digits(text, 0..4)? as i32,
+                month: digits(text, 5..7)?,
+                day: digits(text, 8..10)?,
+            })
+        })();
+
+        date.filter(Date::is_valid).ok_or_else(|| invalid_time(text))
+    }
+}
+
+// Parses an ISO 8601 date or time, converting times with an offset to UTC
+// Times without Z or an offset are floating, and can be given a time zone with Time::in_zone
+impl FromStr for Time {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Time, Error> {
+        if text.len() == 10 {
+            return Ok(Time::Date(text.parse()?));
+        }
+
+        let (date, rest) = text.split_once('T').ok_or_else(|| invalid_time(text))?;
+        let date: Date = date.parse().map_err(|_| invalid_time(text))?;
+
+        // The time of day is 5 or 8 characters, then Z, an offset or nothing
+        let length = rest.find(['Z', '+', '-']).unwrap_or(rest.len());
+        let (clock, zone) = rest.split_at(length);
+
+        let time = (|| {
+            let seconds = match clock.len() {
+                5 => 0,
+                8 if clock.get(5..6) == Some(":") => digits(clock, 6..8)?,
+                _ => return None,
+            };
+
+            if clock.get(2..3) != Some(":") {
+                return None;
+            }
+
+            Some(DateTime {
+                date,
+                hour: digits(clock, 0..2)?,
+                minute: digits(clock, 3..5)?,
+                second: seconds,
+            })
+        })()
+        .filter(DateTime::is_valid)
+        .ok_or_else(|| invalid_time(text))?;
+
+        match zone {
+            "" => Ok(Time::Floating(time)),
+            "Z" => Ok(Time::Utc(time)),
+            _ => {
+                // Offsets are how far ahead of UTC the time is, so they come back off to get to UTC
+                let sign = if zone.starts_with('-') { -1 } else { 1 };
+                let hours = digits(zone, 1..3).filter(|_| zone.len() == 6 && zone.get(3..4) == Some(":"));
+                let minutes = digits(zone, 4..6);
+
+                match (hours, minutes) {
+                    (Some(hours), Some(minutes)) if hours < 24 && minutes < 60 => {
+                        Ok(Time::Utc(time.add_seconds(-sign * (hours * 3600 + minutes * 60) as i64)))
+                    }
+                    _ => Err(invalid_time(text)),
+                }
+            }
+        }
+    }
+}
+
+impl Time {
+    // Pins a floating time to a time zone, leaving every other kind of time as it is
+    pub fn in_zone(self, zone: &str) -> Time {
+        match self {
+            Time::Floating(time) => Time::Zoned {
+                time,
+                zone: zone.to_string(),
+            },
+            time => time,
+        }
+    }
+
+    // Time zone names end up as a property parameter, so they can't have anything that would end it early
+    fn check(&self) -> Result<(), Error> {
+        match self {
+            Time::Zoned { zone, .. } if zone.is_empty() || zone.contains([';', ':', '"', ',', '\r', '\n']) => {
+                Err(Error::InvalidField {
+                    field: "time zone",
+                    reason: format!("{} isn't a time zone name", zone),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The property parameters and value, written after DTSTART or DTEND
+    fn property(&self) -> String {
+        let date = |date: &Date| format!("{:04}{:02}{:02}", date.year, date.month, date.day);
+        let time = |time: &DateTime| format!("{}T{:02}{:02}{:02}", date(&time.date), time.hour, time.minute, time.second);
+
+        match self {
+            Time::Date(day) => format!(";VALUE=DATE:{}", date(day)),
+            Time::Utc(utc) => format!(":{}Z", time(utc)),
+            Time::Zoned { time: local, zone } => format!(";TZID={}:{}", zone, time(local)),
+            Time::Floating(local) => format!(":{}", time(local)),
+        }
+    }
+
+    // Whether this comes after the other time, if they're the sort of times that can be compared
+    fn is_after(&self, other: &Time) -> Option<bool> {
+        match (self, other) {
+            (Time::Date(a), Time::Date(b)) => Some(a > b),
+            (Time::Utc(a), Time::Utc(b)) | (Time::Floating(a), Time::Floating(b)) => Some(a > b),
+            (Time::Zoned { time: a, zone: x }, Time::Zoned { time: b, zone: y }) if x == y => Some(a > b),
+            _ => None,
+        }
+    }
+}
+
+// A calendar event, written as a bare iCalendar VEVENT like phone cameras expect
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub summary: String,
+    pub start: Time,
+    // End of the event, which for all-day events is the day after the last one
+    pub end: Option<Time>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Payload for Event {
+    fn encode(&self) -> Result<String, Error> {
+        if self.summary.is_empty() {
+            return Err(Error::MissingField("summary"));
+        }
+
+        self.start.check()?;
+
+        if let Some(end) = &self.end {
+            end.check()?;
+
+            if matches!(self.start, Time::Date(_)) != matches!(end, Time::Date(_)) {
+                return Err(Error::InvalidField {
+                    field: "end",
+                    reason: "all-day events need dates for both the start and the end".to_string(),
+                });
+            }
+
+            if end.is_after(&self.start) == Some(false) {
+                return Err(Error::InvalidField {
+                    field: "end",
+                    reason: "has to be after the start".to_string(),
+                });
+            }
+        }
+
+        let mut lines = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("SUMMARY:{}", payload::escape_text(&self.summary)),
+            format!("DTSTART{}", self.start.property()),
+        ];
+
+        if let Some(end) = &self.end {
+            lines.push(format!("DTEND{}", end.property()));
+        }
+
+        if let Some(location) = &self.location {
+            lines.push(format!("LOCATION:{}", payload::escape_text(location)));
+        }
+
+        if let Some(description) = &self.description {
+            lines.push(format!("DESCRIPTION:{}", payload::escape_text(description)));
+        }
+
+        lines.push("END:VEVENT".to_string());
+        Ok(lines.iter().map(|line| payload::fold(line)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    fn time(date: Date, hour: u32, minute: u32) -> DateTime {
+        DateTime { date, hour, minute, second: 0 }
+    }
+
+    #[test]
+    fn counts_days() {
+        assert_eq!(date(1970, 1, 1).days(), 0);
+        assert_eq!(date(1969, 12, 31).days(), -1);
+        assert_eq!(date(2000, 3, 1).days(), 11_017);
+        assert_eq!(date(2024, 2, 29).days(), 19_782);
+        assert_eq!(date(1, 1, 1).days(), -719_162);
+
+        for days in (-719_162..2_932_897).step_by(997).chain([-1, 0, 59, 60, 11_016, 19_782]) {
+            let date = Date::from_days(days);
+            assert!(date.is_valid(), "{:?}", date);
+            assert_eq!(date.days(), days);
+        }
+
+        assert!(!date(2023, 2, 29).is_valid());
+        assert!(!date(1900, 2, 29).is_valid());
+        assert!(date(2000, 2, 29).is_valid());
+    }
+
+    #[test]
+    fn converts_offsets_to_utc() {
+        let parse = |text: &str| text.parse::<Time>().unwrap();
+
+        assert_eq!(parse("2024-05-01T09:30+02:00"), Time::Utc(time(date(2024, 5, 1), 7, 30)));
+        assert_eq!(parse("2024-05-01T01:30+02:00"), Time::Utc(time(date(2024, 4, 30), 23, 30)));
+        assert_eq!(parse("2024-12-31T23:30-01:00"), Time::Utc(time(date(2025, 1, 1), 0, 30)));
+        assert_eq!(parse("2024-02-28T23:00-01:30"), Time::Utc(time(date(2024, 2, 29), 0, 30)));
+        let seconds = DateTime {
+            second: 15,
+            ..time(date(2024, 5, 1), 9, 30)
+        };
+        assert_eq!(parse("2024-05-01T09:30:15Z"), Time::Utc(seconds));
+        assert_eq!(parse("2024-05-01T09:30"), Time::Floating(time(date(2024, 5, 1), 9, 30)));
+        assert_eq!(parse("2024-05-01"), Time::Date(date(2024, 5, 1)));
+
+        for bad in ["2024-5-01", "2024-02-30", "2024-05-01T24:00", "2024-05-01T09:30+2:00", "2024-05-01T09:30+24:00"] {
+            assert!(bad.parse::<Time>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn writes_each_kind_of_time() {
+        let all_day = Event {
+            summary: "Holiday".to_string(),
+            start: Time::Date(date(2024, 5, 1)),
+            end: Some(Time::Date(date(2024, 5, 2))),
+            location: None,
+            description: None,
+        };
+        assert_eq!(
+            all_day.encode().unwrap(),
+            concat!(
+                "BEGIN:VEVENT\r\n",
+                "SUMMARY:Holiday\r\n",
+                "DTSTART;VALUE=DATE:20240501\r\n",
+                "DTEND;VALUE=DATE:20240502\r\n",
+                "END:VEVENT\r\n",
+            )
+        );
+
+        // A start at a time of day and an all-day end can't be mixed
+        let zoned = Event {
+            start: "2024-05-01T09:30".parse::<Time>().unwrap().in_zone("Europe/Zurich"),
+            ..all_day.clone()
+        };
+        assert!(zoned.encode().is_err());
+
+        let zoned = Event {
+            end: Some("2024-05-01T10:00".parse::<Time>().unwrap().in_zone("Europe/Zurich")),
+            ..zoned
+        };
+        let encoded = zoned.encode().unwrap();
+        assert!(encoded.contains("\r\nDTSTART;TZID=Europe/Zurich:20240501T093000\r\n"));
+        assert!(encoded.contains("\r\nDTEND;TZID=Europe/Zurich:20240501T100000\r\n"));
+
+        let utc = Event {
+            start: Time::Utc(time(date(2024, 5, 1), 7, 30)),
+            end: None,
+            ..all_day.clone()
+        };
+        assert!(utc.encode().unwrap().contains("\r\nDTSTART:20240501T073000Z\r\n"));
+
+        let backwards = Event {
+            start: Time::Date(date(2024, 5, 2)),
+            end: Some(Time::Date(date(2024, 5, 1))),
+            ..all_day.clone()
+        };
+        assert!(backwards.encode().is_err());
+
+        let bad_zone = Event {
+            start: Time::Floating(time(date(2024, 5, 1), 9, 30)).in_zone("Europe/Zurich:x"),
+            end: None,
+            ..all_day
+        };
+        assert!(bad_zone.encode().is_err());
+    }
+
+    #[test]
+    fn escapes_and_folds_text() {
+        let event = Event {
+            summary: "Lunch, then; talk\nat noon".to_string(),
+            start: Time::Date(date(2024, 5, 1)),
+            end: None,
+            location: Some("Café Zürich, Bahnhofstrasse 1".to_string()),
+            description: Some("é".repeat(40)),
+        };
+        let encoded = event.encode().unwrap();
+
+        assert!(encoded.contains(&format!("\r\n{}\r\n", r"SUMMARY:Lunch\, then\; talk\nat noon")));
+        assert!(encoded.contains("\r\nLOCATION:Café Zürich\\, Bahnhofstrasse 1\r\n"));
+
+        // 12 octets of DESCRIPTION: and 31 é fill the first line to 74 octets, and the next é doesn't fit
+        let description = format!("\r\nDESCRIPTION:{}\r\n {}\r\n", "é".repeat(31), "é".repeat(9));
+        assert!(encoded.contains(&description));
+        assert!(encoded.split("\r\n").all(|line| line.len() <= 75));
+    }
+}
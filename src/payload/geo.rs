@@ -0,0 +1,96 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+
+// A point on the map as an RFC 5870 geo: URI, which opens in the phone's maps app
+#[derive(Clone, Debug, Default)]
+pub struct Geo {
+    // Degrees north of the equator, negative for south
+    pub latitude: f64,
+    // Degrees east of Greenwich, negative for west
+    pub longitude: f64,
+    // Metres above sea level
+    pub altitude: Option<f64>,
+    // Search or label for the maps app, which Android shows as the name of the place
+    pub query: Option<String>,
+}
+
+impl Payload for Geo {
+    fn encode(&self) -> Result<String, Error> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(Error::InvalidField {
+                field: "latitude",
+                reason: "has to be from -90 to 90".to_string(),
+            });
+        }
+
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(Error::InvalidField {
+                field: "longitude",
+                reason: "has to be from -180 to 180".to_string(),
+            });
+        }
+
+        let mut encoded = format!("geo:{},{}", self.latitude, self.longitude);
+
+        if let Some(altitude) = self.altitude {
+            if !altitude.is_finite() {
+                return Err(Error::InvalidField {
+                    field: "altitude",
+                    reason: "has to be a number".to_string(),
+                });
+            }
+
+            encoded += &format!(",{}", altitude);
+        }
+
+        if let Some(query) = &self.query {
+            encoded += &format!("?q={}", payload::percent_encode(query, ""));
+        }
+
+        Ok(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(latitude: f64, longitude: f64) -> Geo {
+        Geo {
+            latitude,
+            longitude,
+            ..Geo::default()
+        }
+    }
+
+    #[test]
+    fn writes_points() {
+        assert_eq!(geo(47.3769, 8.5417).encode().unwrap(), "geo:47.3769,8.5417");
+        assert_eq!(geo(-33.8568, -151.2153).encode().unwrap(), "geo:-33.8568,-151.2153");
+
+        let labelled = Geo {
+            altitude: Some(408.0),
+            query: Some("Zürich HB & more".to_string()),
+            ..geo(47.3769, 8.5417)
+        };
+        assert_eq!(labelled.encode().unwrap(), "geo:47.3769,8.5417,408?q=Z%C3%BCrich%20HB%20%26%20more");
+    }
+
+    #[test]
+    fn checks_ranges() {
+        assert!(geo(90.0, 180.0).encode().is_ok());
+        assert!(geo(-90.0, -180.0).encode().is_ok());
+
+        assert!(matches!(geo(90.5, 0.0).encode(), Err(Error::InvalidField { field: "latitude", .. })));
+        assert!(matches!(geo(-90.5, 0.0).encode(), Err(Error::InvalidField { field: "latitude", .. })));
+        assert!(matches!(geo(f64::NAN, 0.0).encode(), Err(Error::InvalidField { field: "latitude", .. })));
+        assert!(matches!(geo(0.0, 180.5).encode(), Err(Error::InvalidField { field: "longitude", .. })));
+        assert!(matches!(geo(0.0, -180.5).encode(), Err(Error::InvalidField { field: "longitude", .. })));
+
+        let infinite = Geo {
+            altitude: Some(f64::INFINITY),
+            ..geo(0.0, 0.0)
+        };
+        assert!(matches!(infinite.encode(), Err(Error::InvalidField { field: "altitude", .. })));
+    }
+}
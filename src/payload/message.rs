@@ -0,0 +1,183 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+
+// Strips the spaces and punctuation people put in phone numbers, keeping a leading + for international numbers
+fn phone_number(number: &str) -> Result<String, Error> {
+    let number: String = number.chars().filter(|c| !" -.()".contains(*c)).collect();
+    let digits = number.strip_prefix('+').unwrap_or(&number);
+
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(Error::InvalidField {
+            field: "phone number",
+            reason: format!("{} isn't a phone number", number),
+        });
+    }
+
+    Ok(number)
+}
+
+// A text message ready to send, in the SMSTO: format phone cameras understand
+#[derive(Clone, Debug, Default)]
+pub struct Sms {
+    pub number: String,
+    pub message: Option<String>,
+}
+
+impl Payload for Sms {
+    fn encode(&self) -> Result<String, Error> {
+        let number = phone_number(&self.number)?;
+
+        // Everything after the second colon is the message, so it doesn't need escaping
+        match &self.message {
+            Some(message) => Ok(format!("SMSTO:{}:{}", number, message)),
+            None => Ok(format!("SMSTO:{}:", number)),
+        }
+    }
+}
+
+// A phone number to call, as an RFC 3966 tel: URI
+#[derive(Clone, Debug, Default)]
+pub struct Tel {
+    pub number: String,
+}
+
+impl Payload for Tel {
+    fn encode(&self) -> Result<String, Error> {
+        Ok(format!("tel:{}", phone_number(&self.number)?))
+    }
+}
+
+// An email ready to send, as an RFC 6068 mailto: URI
+#[derive(Clone, Debug, Default)]
+pub struct Mailto {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+// Email addresses can keep their @ and the other some-delims RFC 6068 lets through unescaped
+// Commas are some-delims too, but they'd split the address in two, so they're escaped along with everything else
+fn addresses(field: &'static str, addresses: &[String]) -> Result<String, Error> {
+    if let Some(address) = addresses.iter().find(|address| !address.contains('@')) {
+        return Err(Error::InvalidField {
+            field,
+            reason: format!("{} isn't an email address", address),
+        });
+    }
+
+    Ok(addresses
+        .iter()
+        .map(|address| payload::percent_encode(address, "@!$'()*+;:"))
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+impl Payload for Mailto {
+    fn encode(&self) -> Result<String, Error> {
+        if self.to.is_empty() && self.cc.is_empty() && self.bcc.is_empty() {
+            return Err(Error::MissingField("recipient"));
+        }
+
+        let mut fields = vec![];
+
+        if !self.cc.is_empty() {
+            fields.push(format!("cc={}", addresses("cc", &self.cc)?));
+        }
+
+        if !self.bcc.is_empty() {
+            fields.push(format!("bcc={}", addresses("bcc", &self.bcc)?));
+        }
+
+        if let Some(subject) = &self.subject {
+            fields.push(format!("subject={}", payload::percent_encode(subject, "")));
+        }
+
+        // Line breaks in the body have to be CRLF, whatever they were to begin with
+        if let Some(body) = &self.body {
+            let body = body.replace("\r\n", "\n").replace('\n', "\r\n");
+            fields.push(format!("body={}", payload::percent_encode(&body, "")));
+        }
+
+        let mut encoded = format!("mailto:{}", addresses("to", &self.to)?);
+
+        if !fields.is_empty() {
+            encoded += &format!("?{}", fields.join("&"));
+        }
+
+        Ok(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_texts_and_calls() {
+        let sms = Sms {
+            number: "+41 (44) 668-18-00".to_string(),
+            message: Some("Running late: 10 min".to_string()),
+        };
+        assert_eq!(sms.encode().unwrap(), "SMSTO:+41446681800:Running late: 10 min");
+
+        let empty = Sms {
+            message: None,
+            ..sms
+        };
+        assert_eq!(empty.encode().unwrap(), "SMSTO:+41446681800:");
+
+        let tel = Tel {
+            number: "044 668.18.00".to_string(),
+        };
+        assert_eq!(tel.encode().unwrap(), "tel:0446681800");
+
+        for bad in ["", "+", "12a", "1+2"] {
+            assert!(Tel { number: bad.to_string() }.encode().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn writes_emails() {
+        let mailto = Mailto {
+            to: vec!["ada@example.com".to_string(), "charles@example.com".to_string()],
+            cc: vec!["o'brien+qr@example.com".to_string()],
+            bcc: vec![],
+            subject: Some("Lunch & notes?".to_string()),
+            body: Some("One\ntwo\r\nthree".to_string()),
+        };
+        assert_eq!(
+            mailto.encode().unwrap(),
+            concat!(
+                "mailto:ada@example.com,charles@example.com?cc=o'brien+qr@example.com",
+                "&subject=Lunch%20%26%20notes%3F&body=One%0D%0Atwo%0D%0Athree"
+            )
+        );
+
+        let bcc = Mailto {
+            bcc: vec!["ada@example.com".to_string()],
+            ..Mailto::default()
+        };
+        assert_eq!(bcc.encode().unwrap(), "mailto:?bcc=ada@example.com");
+
+        assert_eq!(Mailto::default().encode(), Err(Error::MissingField("recipient")));
+        let bad = Mailto {
+            to: vec!["ada".to_string()],
+            ..Mailto::default()
+        };
+        assert!(matches!(bad.encode(), Err(Error::InvalidField { field: "to", .. })));
+    }
+
+    #[test]
+    fn escapes_what_addresses_cant_hold() {
+        let mailto = Mailto {
+            to: vec!["a|b{c}@example.com".to_string(), "x=y^z`,\"q\"(w)@example.com".to_string()],
+            ..Mailto::default()
+        };
+        assert_eq!(
+            mailto.encode().unwrap(),
+            "mailto:a%7Cb%7Bc%7D@example.com,x%3Dy%5Ez%60%2C%22q%22(w)@example.com"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+use std::str::FromStr;
+
+// Bytes of secret to generate, which is the 160 bits RFC 4226 recommends
+pub const SECRET_LENGTH: usize = 20;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Algorithm, String> {
+        match name.to_ascii_uppercase().replace('-', "").as_str() {
+            "SHA1" => Ok(Algorithm::Sha1),
+            "SHA256" => Ok(Algorithm::Sha256),
+            "SHA512" => Ok(Algorithm::Sha512),
+            _ => Err(format!("Unknown algorithm {}", name)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    // Time-based codes, changing every period seconds
+    Totp { period: u32 },
+    // Counter-based codes, starting from the counter
+    Hotp { counter: u64 },
+}
+
+// A one-time password account to add to an authenticator app, as an otpauth:// URI
+#[derive(Clone, Debug)]
+pub struct Otp {
+    pub kind: Kind,
+    // Service the account is for, shown above the account name in most apps
+    pub issuer: Option<String>,
+    pub account: String,
+    pub secret: Vec<u8>,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+}
+
+impl Default for Otp {
+    fn default() -> Otp {
+        Otp {
+            kind: Kind::Totp { period: 30 },
+            issuer: None,
+            account: String::new(),
+            secret: vec![],
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+        }
+    }
+}
+
+// Encodes bytes as RFC 4648 base32 without padding, which is how authenticator apps want secrets
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len() * 8 / 5 + 1);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+
+    // Whatever's left over is padded out to 5 bits with zeroes
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    encoded
+}
+
+// Decodes base32, ignoring case, spaces and padding since secrets are often written out in groups
+pub fn base32_decode(text: &str) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for character in text.chars().filter(|&c| c != ' ' && c != '-' && c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&letter| letter as char == character.to_ascii_uppercase())
+            .ok_or_else(|| Error::InvalidField {
+                field: "secret",
+                reason: format!("{} isn't a base32 character", character),
+            })?;
+
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+// Makes a new random secret from the operating system's random number generator
+pub fn generate_secret() -> Result<Vec<u8>, Error> {
+    let mut secret = vec![0; SECRET_LENGTH];
+
+    getrandom::getrandom(&mut secret).map_err(|error| Error::InvalidField {
+        field: "secret",
+        reason: format!("couldn't get randomness from the operating system: {}", error),
+    })?;
+
+    Ok(secret)
+}
+
+impl Payload for Otp {
+    fn encode(&self) -> Result<String, Error> {
+        if self.account.is_empty() {
+            return Err(Error::MissingField("account"));
+        }
+
+        // Anything shorter than 80 bits is too easy to guess, as RFC 4226 puts it
+        if self.secret.len() < 10 {
+            return Err(Error::InvalidField {
+                field: "secret",
+                reason: "has to be at least 10 bytes".to_string(),
+            });
+        }
+
+        if !(6..=8).contains(&self.digits) {
+            return Err(Error::InvalidField {
+                field: "digits",
+                reason: "has to be from 6 to 8".to_string(),
+            });
+        }
+
+        // The issuer and account are split by a colon in the label, so the issuer can't have one of its own
+        if self.issuer.as_ref().is_some_and(|issuer| issuer.is_empty() || issuer.contains(':')) {
+            return Err(Error::InvalidField {
+                field: "issuer",
+                reason: "can't be empty or contain a colon".to_string(),
+            });
+        }
+
+        // Everything but unreserved characters is escaped, @ included, so apps that decode the label strictly still can
+        let account = payload::percent_encode(&self.account, "");
+
+        let label = match &self.issuer {
+            Some(issuer) => format!("{}:{}", payload::percent_encode(issuer, ""), account),
+            None => account,
+        };
+
+        let kind = match self.kind {
+            Kind::Totp { .. } => "totp",
+            Kind::Hotp { .. } => "hotp",
+        };
+
+        let mut encoded = format!("otpauth://{}/{}?secret={}", kind, label, base32_encode(&self.secret));
+
+        // The issuer goes in the parameters as well, since older apps only read it from there
+        if let Some(issuer) = &self.issuer {
+            encoded += &format!("&issuer={}", payload::percent_encode(issuer, ""));
+        }
+
+        // Defaults are left out, since some apps give up on parameters they don't expect
+        match self.algorithm {
+            Algorithm::Sha1 => (),
+            Algorithm::Sha256 => encoded += "&algorithm=SHA256",
+            Algorithm::Sha512 => encoded += "&algorithm=SHA512",
+        }
+
+        if self.digits != 6 {
+            encoded += &format!("&digits={}", self.digits);
+        }
+
+        match self.kind {
+            Kind::Totp { period: 0 } => {
+                return Err(Error::InvalidField {
+                    field: "period",
+                    reason: "has to be at least 1 second".to_string(),
+                })
+            }
+            Kind::Totp { period: 30 } => (),
+            Kind::Totp { period } => encoded += &format!("&period={}", period),
+            Kind::Hotp { counter } => encoded += &format!("&counter={}", counter),
+        }
+
+        Ok(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The secret from the test vectors in RFC 6238
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    fn otp() -> Otp {
+        Otp {
+            issuer: Some("Issuer".to_string()),
+            account: "alice@example.com".to_string(),
+            secret: SECRET.to_vec(),
+            ..Otp::default()
+        }
+    }
+
+    #[test]
+    fn encodes_base32() {
+        // The vectors from RFC 4648 section 10, without the padding
+        let vectors = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+
+        for (bytes, encoded) in vectors.iter() {
+            assert_eq!(base32_encode(bytes.as_bytes()), *encoded);
+            assert_eq!(base32_decode(encoded), Ok(bytes.as_bytes().to_vec()));
+        }
+
+        assert_eq!(base32_encode(SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn decodes_base32_however_its_written() {
+        assert_eq!(base32_decode("MZXW6YTBOI======"), Ok(b"foobar".to_vec()));
+        assert_eq!(base32_decode("mzxw 6ytb-oi"), Ok(b"foobar".to_vec()));
+        assert!(base32_decode("MZXW1").is_err());
+        assert!(base32_decode("MZXW6!").is_err());
+
+        let secret = generate_secret().unwrap();
+        assert_eq!(secret.len(), SECRET_LENGTH);
+        assert_ne!(secret, generate_secret().unwrap());
+        assert_eq!(base32_decode(&base32_encode(&secret)), Ok(secret));
+    }
+
+    #[test]
+    fn writes_uris() {
+        assert_eq!(
+            otp().encode().unwrap(),
+            "otpauth://totp/Issuer:alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Issuer"
+        );
+
+        let everything = Otp {
+            kind: Kind::Hotp { counter: 7 },
+            issuer: Some("Big Co & Sons".to_string()),
+            algorithm: Algorithm::Sha256,
+            digits: 8,
+            ..otp()
+        };
+        assert_eq!(
+            everything.encode().unwrap(),
+            concat!(
+                "otpauth://hotp/Big%20Co%20%26%20Sons:alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+                "&issuer=Big%20Co%20%26%20Sons&algorithm=SHA256&digits=8&counter=7"
+            )
+        );
+
+        let period = Otp {
+            issuer: None,
+            kind: Kind::Totp { period: 60 },
+            ..otp()
+        };
+        assert_eq!(
+            period.encode().unwrap(),
+            "otpauth://totp/alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&period=60"
+        );
+    }
+
+    #[test]
+    fn checks_settings() {
+        for digits in [0, 5, 9] {
+            let otp = Otp { digits, ..otp() };
+            assert!(matches!(otp.encode(), Err(Error::InvalidField { field: "digits", .. })));
+        }
+
+        let field = |otp: Otp| match otp.encode() {
+            Err(Error::InvalidField { field, .. }) => Some(field),
+            _ => None,
+        };
+        assert_eq!(field(Otp { kind: Kind::Totp { period: 0 }, ..otp() }), Some("period"));
+        assert_eq!(field(Otp { secret: vec![0; 9], ..otp() }), Some("secret"));
+        assert_eq!(field(Otp { issuer: Some("a:b".to_string()), ..otp() }), Some("issuer"));
+        assert_eq!(field(Otp { issuer: Some(String::new()), ..otp() }), Some("issuer"));
+        assert_eq!(Otp { account: String::new(), ..otp() }.encode(), Err(Error::MissingField("account")));
+
+        assert_eq!("sha-256".parse(), Ok(Algorithm::Sha256));
+        assert!("md5".parse::<Algorithm>().is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+use crate::qr::EcLevel;
+
+// Longest payload a QR-bill can hold, which is all a version 25 code fits at EC level M
+pub const MAX_LENGTH: usize = 997;
+
+// Largest amount that can be asked for, in cents
+pub const MAX_AMOUNT: u64 = 99_999_999_999;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Currency {
+    Chf,
+    Eur,
+}
+
+// A structured address, the only kind newer QR-bills are allowed to use
+#[derive(Clone, Debug, Default)]
+pub struct Address {
+    pub name: String,
+    pub street: Option<String>,
+    pub building_number: Option<String>,
+    pub postal_code: String,
+    pub town: String,
+    // Two letter ISO 3166 country code
+    pub country: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum Reference {
+    // 27 digit QR reference, which has to be used with a QR-IBAN and only with one
+    Qr(String),
+    // ISO 11649 creditor reference starting with RF
+    Creditor(String),
+}
+
+// A Swiss QR-bill, in version 2.0 of the Swiss Payment Code
+#[derive(Clone, Debug)]
+pub struct Bill {
+    // IBAN or QR-IBAN of the creditor, which has to be Swiss or from Liechtenstein
+    pub iban: String,
+    pub creditor: Address,
+    // Amount in cents, or None to leave it for the payer to fill in
+    pub amount: Option<u64>,
+    pub currency: Currency,
+    pub debtor: Option<Address>,
+    pub reference: Option<Reference>,
+    // Message for the creditor, which can't be used to match up the payment
+    pub message: Option<String>,
+    // Structured bill information for the creditor's software, like Swico's //S1/ format
+    pub bill_information: Option<String>,
+}
+
+impl Default for Bill {
+    fn default() -> Bill {
+        Bill {
+            iban: String::new(),
+            creditor: Address::default(),
+            amount: None,
+            currency: Currency::Chf,
+            debtor: None,
+            reference: None,
+            message: None,
+            bill_information: None,
+        }
+    }
+}
+
+fn check_length(field: &'static str, value: &str, max: usize) -> Result<(), Error> {
+    if value.chars().count() > max {
+        return Err(Error::InvalidField {
+            field,
+            reason: format!("can be at most {} characters", max),
+        });
+    }
+
+    if value.contains(['\r', '\n']) {
+        return Err(Error::InvalidField {
+            field,
+            reason: "can't contain line breaks".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+// QR-IBANs have an institution ID from 30000 to 31999 in place of the usual bank clearing number
+pub fn is_qr_iban(iban: &str) -> bool {
+    iban.get(4..9)
+        .and_then(|id| id.parse::<u32>().ok())
+        .is_some_and(|id| (30000..=31999).contains(&id))
+}
+
+// Checks the last digit of a QR reference against the recursive mod 10 check digit of the rest
+pub fn validate_qr_reference(reference: &str) -> Result<(), Error> {
+    const TABLE: [u32; 10] = [0, 9, 4, 6, 8, 2, 7, 1, 3, 5];
+
+    let invalid = |reason: &str| Error::InvalidField {
+        field: "QR reference",
+        reason: reason.to_string(),
+    };
+
+    let digits: Option<Vec<u32>> = reference.chars().map(|c| c.to_digit(10)).collect();
+
+    let digits = match digits {
+        Some(digits) if digits.len() == 27 => digits,
+        _ => return Err(invalid("QR references are 27 digits")),
+    };
+
+    let carry = digits[..26].iter().fold(0, |carry, &digit| TABLE[((carry + digit) % 10) as usize]);
+
+    if (10 - carry) % 10 != digits[26] {
+        return Err(invalid("check digit doesn't match"));
+    }
+
+    Ok(())
+}
+
+// Checks a creditor reference is RF, 2 check digits and up to 21 letters or digits that come out as 1 mod 97
+pub fn validate_creditor_reference(reference: &str) -> Result<(), Error> {
+    let bytes = reference.as_bytes();
+    let valid = (5..=25).contains(&bytes.len())
+        && reference.starts_with("RF")
+        && bytes[2..4].iter().all(u8::is_ascii_digit)
+        && bytes.iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+        && payload::mod97(reference) == 1;
+
+    if !valid {
+        return Err(Error::InvalidField {
+            field: "creditor reference",
+            reason: "has to be RF, 2 check digits and up to 21 letters or digits, with matching check digits".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+impl Address {
+    // The 7 lines of an address, starting with S for structured
+    fn lines(&self, field: &'static str) -> Result<Vec<String>, Error> {
+        if self.name.is_empty() || self.postal_code.is_empty() || self.town.is_empty() {
+            return Err(Error::MissingField(field));
+        }
+
+        if self.country.len() != 2 || !self.country.bytes().all(|byte| byte.is_ascii_uppercase()) {
+            return Err(Error::InvalidField {
+                field,
+                reason: "country has to be a 2 letter country code".to_string(),
+            });
+        }
+
+        let street = self.street.clone().unwrap_or_default();
+        let building_number = self.building_number.clone().unwrap_or_default();
+
+        check_length(field, &self.name, 70)?;
+        check_length(field, &street, 70)?;
+        check_length(field, &building_number, 16)?;
+        check_length(field, &self.postal_code, 16)?;
+        check_length(field, &self.town, 35)?;
+
+        Ok(vec![
+            "S".to_string(),
+            self.name.clone(),
+            street,
+            building_number,
+            self.postal_code.clone(),
+            self.town.clone(),
+            self.country.clone(),
+        ])
+    }
+}
+
+impl Payload for Bill {
+    fn encode(&self) -> Result<String, Error> {
+        let iban = payload::validate_iban(&self.iban)?;
+
+        if !iban.starts_with("CH") && !iban.starts_with("LI") {
+            return Err(Error::InvalidField {
+                field: "IBAN",
+                reason: "QR-bills can only pay into Swiss and Liechtenstein accounts".to_string(),
+            });
+        }
+
+        let (reference_type, reference) = match (&self.reference, is_qr_iban(&iban)) {
+            (Some(Reference::Qr(reference)), true) => {
+                validate_qr_reference(reference)?;
+                ("QRR", reference.as_str())
+            }
+            (Some(Reference::Creditor(reference)), false) => {
+                validate_creditor_reference(reference)?;
+                ("SCOR", reference.as_str())
+            }
+            (None, false) => ("NON", ""),
+            (_, true) => {
+                return Err(Error::InvalidField {
+                    field: "reference",
+                    reason: "QR-IBANs need a QR reference".to_string(),
+                })
+            }
+            (Some(Reference::Qr(_)), false) => {
+                return Err(Error::InvalidField {
+                    field: "reference",
+                    reason: "QR references can only be used with a QR-IBAN".to_string(),
+                })
+            }
+        };
+
+        let amount = match self.amount {
+            Some(amount) if amount == 0 || amount > MAX_AMOUNT => {
+                return Err(Error::InvalidField {
+                    field: "amount",
+                    reason: "has to be from 0.01 to 999999999.99".to_string(),
+                })
+            }
+            Some(amount) => payload::format_amount(amount),
+            None => String::new(),
+        };
+
+        // The message and bill information share 140 characters between them
+        let message = self.message.clone().unwrap_or_default();
+        let bill_information = self.bill_information.clone().unwrap_or_default();
+        check_length("message", &message, 140)?;
+        check_length("bill information", &bill_information, 140)?;
+
+        if message.chars().count() + bill_information.chars().count() > 140 {
+            return Err(Error::InvalidField {
+                field: "message",
+                reason: "and bill information can be at most 140 characters together".to_string(),
+            });
+        }
+
+        let mut lines = vec!["SPC".to_string(), "0200".to_string(), "1".to_string(), iban];
+        lines.extend(self.creditor.lines("creditor")?);
+
+        // The ultimate creditor isn't allowed to be used yet, but its lines still have to be there
+        lines.extend(vec![String::new(); 7]);
+
+        lines.push(amount);
+        lines.push(match self.currency {
+            Currency::Chf => "CHF".to_string(),
+            Currency::Eur => "EUR".to_string(),
+        });
+
+        match &self.debtor {
+            Some(debtor) => lines.extend(debtor.lines("debtor")?),
+            None => lines.extend(vec![String::new(); 7]),
+        }
+
+        lines.push(reference_type.to_string());
+        lines.push(reference.to_string());
+        lines.push(message);
+        lines.push("EPD".to_string());
+
+        if self.bill_information.is_some() {
+            lines.push(bill_information);
+        }
+
+        let encoded = lines.join("\n");
+
+        if encoded.len() > MAX_LENGTH {
+            return Err(Error::PayloadTooLong {
+                length: encoded.len(),
+                max: MAX_LENGTH,
+            });
+        }
+
+        Ok(encoded)
+    }
+
+    fn required_ec_level(&self) -> Option<EcLevel> {
+        Some(EcLevel::M)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The examples from the Swiss Implementation Guidelines for the QR-bill
+    const IBAN: &str = "CH93 0076 2011 6238 5295 7";
+    const QR_IBAN: &str = "CH44 3199 9123 0008 8901 2";
+    const QR_REFERENCE: &str = "210000000003139471430009017";
+    const CREDITOR_REFERENCE: &str = "RF18539007547034";
+
+    fn bill() -> Bill {
+        Bill {
+            iban: IBAN.to_string(),
+            creditor: Address {
+                name: "Robert Schneider AG".to_string(),
+                street: Some("Rue du Lac".to_string()),
+                building_number: Some("1268".to_string()),
+                postal_code: "2501".to_string(),
+                town: "Biel".to_string(),
+                country: "CH".to_string(),
+            },
+            amount: Some(394_975),
+            ..Bill::default()
+        }
+    }
+
+    #[test]
+    fn checks_ibans() {
+        assert!(payload::validate_iban(IBAN).is_ok());
+        assert!(payload::validate_iban(QR_IBAN).is_ok());
+        assert!(payload::validate_iban("CH93 0076 2011 6238 5295 8").is_err());
+
+        assert!(is_qr_iban(&payload::validate_iban(QR_IBAN).unwrap()));
+        assert!(!is_qr_iban(&payload::validate_iban(IBAN).unwrap()));
+
+        let german = Bill {
+            iban: "DE89 3704 0044 0532 0130 00".to_string(),
+            ..bill()
+        };
+        assert!(matches!(german.encode(), Err(Error::InvalidField { field: "IBAN", .. })));
+    }
+
+    #[test]
+    fn checks_qr_references() {
+        assert_eq!(validate_qr_reference(QR_REFERENCE), Ok(()));
+        assert_eq!(validate_qr_reference("000000000000000000000000000"), Ok(()));
+
+        assert!(validate_qr_reference("210000000003139471430009018").is_err());
+        assert!(validate_qr_reference("21000000000313947143000901").is_err());
+        assert!(validate_qr_reference("21000000000313947143000901A").is_err());
+    }
+
+    #[test]
+    fn checks_creditor_references() {
+        assert_eq!(validate_creditor_reference(CREDITOR_REFERENCE), Ok(()));
+        assert_eq!(validate_creditor_reference("RF712348231"), Ok(()));
+
+        assert!(validate_creditor_reference("RF19539007547034").is_err());
+        assert!(validate_creditor_reference("RF18 5390 0754 7034").is_err());
+        assert!(validate_creditor_reference("XX18539007547034").is_err());
+    }
+
+    #[test]
+    fn matches_references_to_ibans() {
+        let qr = Bill {
+            iban: QR_IBAN.to_string(),
+            reference: Some(Reference::Qr(QR_REFERENCE.to_string())),
+            ..bill()
+        };
+        assert!(qr.encode().unwrap().contains("\nQRR\n210000000003139471430009017\n"));
+
+        let creditor = Bill {
+            reference: Some(Reference::Creditor(CREDITOR_REFERENCE.to_string())),
+            ..bill()
+        };
+        assert!(creditor.encode().unwrap().contains("\nSCOR\nRF18539007547034\n"));
+
+        assert!(Bill { iban: QR_IBAN.to_string(), ..bill() }.encode().is_err());
+        assert!(Bill { reference: Some(Reference::Qr(QR_REFERENCE.to_string())), ..bill() }.encode().is_err());
+    }
+
+    #[test]
+    fn shares_140_characters_between_message_and_bill_information() {
+        let bill = |message: usize, bill_information: usize| Bill {
+            message: Some("m".repeat(message)),
+            bill_information: Some("b".repeat(bill_information)),
+            ..bill()
+        };
+
+        assert!(bill(70, 70).encode().is_ok());
+        assert!(bill(140, 0).encode().is_ok());
+        assert!(matches!(bill(71, 70).encode(), Err(Error::InvalidField { field: "message", .. })));
+        assert!(bill(140, 140).encode().is_err());
+        assert!(bill(0, 141).encode().is_err());
+    }
+}
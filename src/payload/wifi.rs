@@ -0,0 +1,227 @@
+use crate::error::Error;
+use crate::payload::{self, Payload};
+use std::str::FromStr;
+
+// Characters that have to be escaped in any value of a Wi-Fi payload
+const SPECIAL: &str = ";,:\"";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Security {
+    Open,
+    Wep,
+    // WPA, WPA2 and WPA3 personal all share this one, phones work out which from the network itself
+    Wpa,
+    // WPA2-Enterprise, which needs the EAP settings
+    Enterprise,
+}
+
+impl FromStr for Security {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Security, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "open" | "nopass" => Ok(Security::Open),
+            "wep" => Ok(Security::Wep),
+            "wpa" | "wpa2" | "wpa3" => Ok(Security::Wpa),
+            "enterprise" | "wpa2-eap" => Ok(Security::Enterprise),
+            _ => Err(format!("Unknown security type {}", name)),
+        }
+    }
+}
+
+// Settings for joining a WPA2-Enterprise network
+#[derive(Clone, Debug, Default)]
+pub struct Eap {
+    // EAP method, like PEAP, TTLS or PWD
+    pub method: String,
+    // Inner authentication used by PEAP and TTLS, like MSCHAPV2
+    pub phase2: Option<String>,
+    pub identity: Option<String>,
+    pub anonymous_identity: Option<String>,
+}
+
+impl Eap {
+    // Whether the method signs in with a password, which EAP-TLS doesn't since it uses a client certificate,
+    // and SIM and AKA don't since they use the phone's SIM card
+    pub fn uses_password(&self) -> bool {
+        let method = self.method.to_ascii_uppercase();
+        let method = method.strip_prefix("EAP-").unwrap_or(&method);
+        !["TLS", "SIM", "AKA", "AKA'"].contains(&method)
+    }
+}
+
+// Checks a WPA password is a passphrase of 8 to 63 characters or a pre-shared key written as 64 hex digits
+fn valid_wpa_password(password: &str) -> bool {
+    let key = password.len() == 64 && password.bytes().all(|byte| byte.is_ascii_hexdigit());
+    (8..=63).contains(&password.len()) || key
+}
+
+// Credentials for a Wi-Fi network, in the WIFI: format that Android and iOS cameras both understand
+#[derive(Clone, Debug)]
+pub struct Wifi {
+    pub ssid: String,
+    pub password: Option<String>,
+    pub security: Security,
+    // Set for networks that don't broadcast their SSID, so phones know to look for them anyway
+    pub hidden: bool,
+    pub eap: Option<Eap>,
+}
+
+impl Default for Wifi {
+    fn default() -> Wifi {
+        Wifi {
+            ssid: String::new(),
+            password: None,
+            security: Security::Wpa,
+            hidden: false,
+            eap: None,
+        }
+    }
+}
+
+impl Payload for Wifi {
+    fn encode(&self) -> Result<String, Error> {
+        if self.ssid.is_empty() {
+            return Err(Error::MissingField("SSID"));
+        }
+
+        let escape = |value: &str| payload::escape(value, SPECIAL);
+
+        let security = match self.security {
+            Security::Open => "nopass",
+            Security::Wep => "WEP",
+            Security::Wpa => "WPA",
+            Security::Enterprise => "WPA2-EAP",
+        };
+
+        let mut encoded = format!("WIFI:T:{};S:{};", security, escape(&self.ssid));
+
+        match (&self.security, &self.password) {
+            (Security::Open, Some(_)) => {
+                return Err(Error::InvalidField {
+                    field: "password",
+                    reason: "open networks don't have one".to_string(),
+                })
+            }
+            (Security::Open, None) => (),
+            (Security::Enterprise, None) if self.eap.as_ref().is_some_and(|eap| !eap.uses_password()) => (),
+            (_, None) => return Err(Error::MissingField("password")),
+            (Security::Wep, Some(password)) if ![5, 10, 13, 26].contains(&password.len()) => {
+                return Err(Error::InvalidField {
+                    field: "password",
+                    reason: "WEP keys are 5 or 13 characters, or 10 or 26 hex digits".to_string(),
+                })
+            }
+            (Security::Wpa, Some(password)) if !valid_wpa_password(password) => {
+                return Err(Error::InvalidField {
+                    field: "password",
+                    reason: "WPA passphrases are 8 to 63 characters, and keys are 64 hex digits".to_string(),
+                })
+            }
+            (_, Some(password)) => encoded += &format!("P:{};", escape(password)),
+        }
+
+        match (&self.security, &self.eap) {
+            (Security::Enterprise, None) => return Err(Error::MissingField("EAP method")),
+            (Security::Enterprise, Some(eap)) => {
+                if eap.method.is_empty() {
+                    return Err(Error::MissingField("EAP method"));
+                }
+
+                encoded += &format!("E:{};", escape(&eap.method));
+
+                if let Some(phase2) = &eap.phase2 {
+                    encoded += &format!("PH2:{};", escape(phase2));
+                }
+
+                if let Some(anonymous_identity) = &eap.anonymous_identity {
+                    encoded += &format!("A:{};", escape(anonymous_identity));
+                }
+
+                if let Some(identity) = &eap.identity {
+                    encoded += &format!("I:{};", escape(identity));
+                }
+            }
+            (_, Some(_)) => {
+                return Err(Error::InvalidField {
+                    field: "EAP method",
+                    reason: "only enterprise networks use EAP".to_string(),
+                })
+            }
+            (_, None) => (),
+        }
+
+        if self.hidden {
+            encoded += "H:true;";
+        }
+
+        // The extra semicolon closes the whole thing off
+        Ok(encoded + ";")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wifi(security: Security, password: Option<&str>) -> Wifi {
+        Wifi {
+            ssid: "Home; \"5G\"".to_string(),
+            password: password.map(str::to_string),
+            security,
+            ..Wifi::default()
+        }
+    }
+
+    fn enterprise(method: &str, password: Option<&str>) -> Wifi {
+        Wifi {
+            eap: Some(Eap {
+                method: method.to_string(),
+                identity: Some("ada@example.com".to_string()),
+                ..Eap::default()
+            }),
+            ..wifi(Security::Enterprise, password)
+        }
+    }
+
+    #[test]
+    fn writes_networks() {
+        assert_eq!(wifi(Security::Open, None).encode().unwrap(), r#"WIFI:T:nopass;S:Home\; \"5G\";;"#);
+        assert_eq!(
+            wifi(Security::Wpa, Some("pass:word")).encode().unwrap(),
+            r#"WIFI:T:WPA;S:Home\; \"5G\";P:pass\:word;;"#
+        );
+        assert_eq!(
+            enterprise("PEAP", Some("secret")).encode().unwrap(),
+            r#"WIFI:T:WPA2-EAP;S:Home\; \"5G\";P:secret;E:PEAP;I:ada@example.com;;"#
+        );
+    }
+
+    #[test]
+    fn checks_wpa_passwords() {
+        let key = "0123456789abcdefABCDEF0123456789abcdefABCDEF0123456789abcdef0123";
+        assert_eq!(key.len(), 64);
+
+        assert!(wifi(Security::Wpa, Some("12345678")).encode().is_ok());
+        assert!(wifi(Security::Wpa, Some(&"x".repeat(63))).encode().is_ok());
+        assert!(wifi(Security::Wpa, Some(key)).encode().unwrap().contains(&format!("P:{};", key)));
+
+        assert!(wifi(Security::Wpa, Some("1234567")).encode().is_err());
+        assert!(wifi(Security::Wpa, Some(&key.replace('0', "g"))).encode().is_err());
+        assert!(wifi(Security::Wpa, Some(&format!("{}0", key))).encode().is_err());
+        assert_eq!(wifi(Security::Wpa, None).encode(), Err(Error::MissingField("password")));
+    }
+
+    #[test]
+    fn only_asks_for_passwords_from_eap_methods_that_use_them() {
+        for method in ["TLS", "eap-tls", "SIM", "AKA'"] {
+            assert!(enterprise(method, None).encode().unwrap().contains(&format!("E:{};", method)));
+        }
+
+        for method in ["PEAP", "TTLS", "PWD"] {
+            assert_eq!(enterprise(method, None).encode(), Err(Error::MissingField("password")));
+        }
+
+        assert_eq!(wifi(Security::Enterprise, Some("secret")).encode(), Err(Error::MissingField("EAP method")));
+    }
+}
@@ -1,666 +1,860 @@
-pub mod qr {
-    use array2d::Array2D;
-    use reed_solomon::Encoder;
-    use std::cmp::Ordering;
-    use std::process::exit;
-    use image;
-
-    type RawImage = Array2D<u8>;
-
-    pub struct QR {
-        pub size: usize,
-        pub version: usize,
-
-        data: Vec<u8>,
-        payload: Vec<u8>,
-        image: RawImage, 
-        masked: RawImage,
-    }
-
-    impl QR {
-        pub fn new(input: String) -> QR {
-            // Table of capacities for versions 1-10 at Q error correction level
-            let capacity_table: [usize; 10] = [11, 20, 32, 46, 60, 74, 86, 108, 130, 151];
-            let mut version = 0;
-
-            for v in 0..capacity_table.len() {
-                if capacity_table[v] > input.len() {
-                    version = v + 1;
-                    break;
-                } 
-            }
+use reed_solomon::Encoder;
+use core::ops::Range;
+use core::str::FromStr;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::matrix::Matrix;
+#[cfg(feature = "std")]
+use crate::render::{self, RenderOptions};
+use crate::segment::{BitWriter, Mode, Segment};
+use serde::Serialize;
+
+
+// Error correction codewords in each block, by level and then version
+const EC_CODEWORDS_PER_BLOCK: [[usize; 40]; 4] = [
+    [7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+// Number of error correction blocks the codewords are split between, by level and then version
+const EC_BLOCKS: [[usize; 40]; 4] = [
+    [1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+// How much of the data can be restored if it gets damaged
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum EcLevel {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl EcLevel {
+    // The next level up, if there is one
+    pub fn next(self) -> Option<EcLevel> {
+        match self {
+            EcLevel::L => Some(EcLevel::M),
+            EcLevel::M => Some(EcLevel::Q),
+            EcLevel::Q => Some(EcLevel::H),
+            EcLevel::H => None,
+        }
+    }
 
-            if version > 4 {
-                println!("Message is too long! (Must be 48 characters or less)");
-                exit(0);
-            }
+    // The 2 bits identifying the level in the format information
+    fn format_bits(self) -> usize {
+        match self {
+            EcLevel::L => 1,
+            EcLevel::M => 0,
+            EcLevel::Q => 3,
+            EcLevel::H => 2,
+        }
+    }
+}
+
+impl FromStr for EcLevel {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<EcLevel, String> {
+        match name {
+            "L" | "l" => Ok(EcLevel::L),
+            "M" | "m" => Ok(EcLevel::M),
+            "Q" | "q" => Ok(EcLevel::Q),
+            "H" | "h" => Ok(EcLevel::H),
+            _ => Err(format!("Unknown error correction level {}", name)),
+        }
+    }
+}
+
+// Which edition of ISO/IEC 18004 to score masks by when picking one
+// They only differ in which finder-like patterns rule 3 counts, so most codes come out the same either way
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PenaltyRules {
+    #[default]
+    Iso2015,
+    Iso2006,
+}
+
+// Everything about how a code was made, for tools that need to know more than what it looks like
+#[derive(Clone, Debug, Serialize)]
+pub struct Metadata {
+    pub version: usize,
+    pub ec_level: EcLevel,
+    pub mask: usize,
+    // Modules along each side, not counting the quiet zone
+    pub size: usize,
+    pub segments: Vec<SegmentMetadata>,
+    // Bits the segments take up, out of the bits of data the code holds at its EC level
+    pub data_bits: usize,
+    pub capacity_bits: usize,
+    pub remaining_bits: usize,
+    pub data_codewords: usize,
+    pub ec_codewords: usize,
+    // Every module, indexed [y][x], true for dark
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modules: Option<Vec<Vec<bool>>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SegmentMetadata {
+    pub mode: Mode,
+    pub characters: usize,
+    pub bits: usize,
+}
+
+pub struct QR {
+    pub size: usize,
+    pub version: usize,
+    pub ec_level: EcLevel,
+    // Mask pattern the code ended up with, once generated
+    pub mask: usize,
+
+    data: Vec<u8>,
+    // What the data was made from, which is empty for codes that weren't made from segments
+    segments: Vec<Segment>,
+    // Number of data codewords holding the message, the rest are padding
+    message_length: usize,
+    // Mask to use instead of picking the best one
+    forced_mask: Option<usize>,
+    // Edition of the standard to score masks by when picking the best one
+    penalty_rules: PenaltyRules,
+    payload: Vec<u8>,
+    // Which block each codeword of the payload came from, and which data codeword it is if it isn't error correction
+    payload_blocks: Vec<usize>,
+    payload_data: Vec<Option<usize>>,
+    // Which codeword of the payload and which bit of it each module holds, if any
+    codewords: Grid<Option<(usize, usize)>>,
+    // Modules before and after masking, with the function patterns flagged
+    image: Matrix,
+    masked: Matrix,
+}
+
+impl QR {
+    pub fn new(input: String) -> Result<QR, Error> {
+        QR::with_ec_level(input, EcLevel::Q)
+    }
 
-            println!("Generating version {} QR code", version);
+    pub fn with_ec_level(input: String, ec_level: EcLevel) -> Result<QR, Error> {
+        QR::with_bytes(input.as_bytes(), ec_level)
+    }
 
-            // Initialise the data with the mode indicator, 0100 (byte mode)
-            let mut data: Vec<u8> = vec![4];
+    // Generates the smallest code holding the data as is in byte mode, which doesn't have to be text
+    pub fn with_bytes(data: &[u8], ec_level: EcLevel) -> Result<QR, Error> {
+        QR::with_segments(vec![Segment::bytes(data)], ec_level)
+    }
 
-            // Create header for QR code
-            if version < 10 {
-                let char_count = input.len() as u8;
-                data.push(char_count);
-            } else {
-                let char_count = input.len() as u16;
-                data.push((char_count >> 8) as u8);
-                data.push((char_count & 0xFF) as u8);
-            }
+    // Generates the smallest code that holds all of the segments
+    pub fn with_segments(segments: Vec<Segment>, ec_level: EcLevel) -> Result<QR, Error> {
+        let version = QR::smallest_version(&segments, ec_level).ok_or(Error::TooLong {
+            max: QR::max_length(ec_level),
+        })?;
 
-            // Add the input to the data
-            data.append(&mut input.as_bytes().to_vec());
+        QR::from_segments(&segments, ec_level, version)
+    }
 
-            // Table of required bytes per EC level
-            let ec_table: [usize; 10] = [13, 22, 34, 48, 62, 76, 88, 110, 132, 154];
+    // Generates at a specific version, which has to be big enough for the input
+    pub fn with_version(input: String, ec_level: EcLevel, version: usize) -> Result<QR, Error> {
+        QR::from_segments(&[Segment::bytes(input.as_bytes())], ec_level, version)
+    }
 
-            // Get the total number of bytes required at version level
-            let total_bytes = ec_table[version - 1];
+    // Generates the segments at a specific version, which has to be big enough for them
+    pub fn from_segments(segments: &[Segment], ec_level: EcLevel, version: usize) -> Result<QR, Error> {
+        if !(1..=40).contains(&version) {
+            return Err(Error::InvalidField {
+                field: "version",
+                reason: format!("{} isn't between 1 and 40", version),
+            });
+        }
 
-            let mut aligned_data: Vec<u8> = vec![];
+        if !QR::fits(segments, ec_level, version) {
+            return Err(Error::TooLongForVersion(version));
+        }
 
-            // Since byte mode is being used, all bytes are shifted 4 to the left to fill the space, and we don't need to calculate terminator padding
-            for byte in 0..data.len() {
-                if byte == data.len() - 1 {
-                    aligned_data.push((data[byte] & 0xF).checked_shl(4).unwrap_or(0));
-                } else {
-                    let aligned_byte = (data[byte] & 0xF).checked_shl(4).unwrap_or(0) + (data[byte + 1] >> 4);
-                    aligned_data.push(aligned_byte);
-                }
-            }
+        let capacity = QR::data_codewords(version, ec_level) * 8;
+        let mut writer = BitWriter::default();
 
-            // Add 236 followed by 17 until total capacity is filled as specified
-            let padding_byte_count = total_bytes - aligned_data.len();
+        for segment in segments {
+            segment.write(version, &mut writer);
+        }
 
-            for i in 0..padding_byte_count {
-                if i % 2 == 0 {
-                    aligned_data.push(236);
-                } else {
-                    aligned_data.push(17);
-                }
-            }
+        // Up to 4 zero bits to end the message, as many as there's room for, then zeroes to the end of the byte
+        writer.push(0, (capacity - writer.length).min(4));
+        writer.push(0, (8 - writer.length % 8) % 8);
 
-            let size = (version - 1) * 4 + 21; 
+        let message_length = writer.bytes.len();
+        let mut data = writer.bytes;
 
-            return QR {
-                size: size,
-                version: version,
-                data: aligned_data,
-                payload: vec![],
-                image: RawImage::filled_with(0, size, size),
-                masked: RawImage::filled_with(0, size, size)
+        // Add 236 followed by 17 until total capacity is filled as specified
+        while data.len() < capacity / 8 {
+            data.push(if (data.len() - message_length).is_multiple_of(2) { 236 } else { 17 });
+        }
+
+        let mut code = QR::from_data(version, ec_level, data, message_length);
+        code.segments = segments.to_vec();
+        Ok(code)
+    }
+
+    // A code with every data codeword set to 0, used as a map of where everything goes when reading codes
+    #[cfg(feature = "std")]
+    pub(crate) fn blank(version: usize, ec_level: EcLevel) -> QR {
+        let mut code = QR::from_data(version, ec_level, vec![0; QR::data_codewords(version, ec_level)], 0);
+        code.generate_error_correction();
+        code.place_modules();
+        code
+    }
+
+    fn from_data(version: usize, ec_level: EcLevel, data: Vec<u8>, message_length: usize) -> QR {
+        let size = (version - 1) * 4 + 21; 
+
+        QR {
+            size,
+            version,
+            ec_level,
+            mask: 0,
+            data,
+            segments: vec![],
+            message_length,
+            forced_mask: None,
+            penalty_rules: PenaltyRules::default(),
+            payload: vec![],
+            payload_blocks: vec![],
+            payload_data: vec![],
+            codewords: Grid::filled_with(None, size, size),
+            image: Matrix::new(size),
+            masked: Matrix::new(size),
+        }
+    }
+
+    // Whether the segments, with their mode indicators and character counts, fit in a version
+    pub fn fits(segments: &[Segment], ec_level: EcLevel, version: usize) -> bool {
+        Segment::total_bits(segments, version).is_some_and(|bits| bits <= QR::data_codewords(version, ec_level) * 8)
+    }
+
+    // Smallest version with room for all of the segments
+    pub fn smallest_version(segments: &[Segment], ec_level: EcLevel) -> Option<usize> {
+        (1..=40).find(|&version| QR::fits(segments, ec_level, version))
+    }
+
+    // Longest input that fits in a version 40 code at this level in byte mode
+    pub fn max_length(ec_level: EcLevel) -> usize {
+        (QR::data_codewords(40, ec_level) * 8 - 4 - Mode::Byte.char_count_bits(40)) / 8
+    }
+
+    // Number of modules left for data and error correction once all the function patterns are placed
+    fn raw_modules(version: usize) -> usize {
+        let mut modules = (16 * version + 128) * version + 64;
+
+        if version >= 2 {
+            let alignment_count = version / 7 + 2;
+            modules -= (25 * alignment_count - 10) * alignment_count - 55;
+
+            // Version information
+            if version >= 7 {
+                modules -= 36;
             }
         }
 
-        fn generate_error_correction(&mut self) {
-            // TODO: Adapt for versions greater than 4
-            // If you think I'm gonna actually implement my own Reed-Solomon algorithm in this, you're kidding yourself
-            let blocks_table = vec![(13, 13, 1, 0), (22, 22, 1, 0), (34, 18, 2, 0), (48, 26, 2, 0)];
+        modules
+    }
 
-            let (capacity, ec_per_block, group_one, group_two) = blocks_table[self.version - 1];
+    // Number of codewords available for data once error correction is taken out
+    fn data_codewords(version: usize, ec_level: EcLevel) -> usize {
+        let level = ec_level as usize;
+        QR::raw_modules(version) / 8 - EC_CODEWORDS_PER_BLOCK[level][version - 1] * EC_BLOCKS[level][version - 1]
+    }
 
-            // Create specified number of EC codewords
-            let enc = Encoder::new(ec_per_block);
+    // Row and column centres of the alignment patterns
+    fn alignment_positions(version: usize) -> Vec<usize> {
+        if version == 1 {
+            return vec![];
+        }
 
-            // Block interleaving is required when there is more than 1 block in a group
-            if group_one > 1 {
-                let data_per_block = capacity / 2;
-                let mut blocks = vec![];
+        let count = version / 7 + 2;
+        let size = version * 4 + 17;
 
-                // Split data into required blocks
-                for block in 0..group_one {
-                    let offset = block*(data_per_block);
-                    let mut new_block = vec![];
+        // The patterns are spaced evenly from the bottom right, with any slack going between the first two
+        let step = if version == 32 {
+            26
+        } else {
+            (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+        };
 
-                    for i in offset..(data_per_block * (1 + block)) {
-                        new_block.push(self.data[i]);
-                    }
+        let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+        positions.push(6);
+        positions.reverse();
+        positions
+    }
 
-                    let mut ecc = enc.encode(&new_block).ecc().to_vec();
-                    new_block.append(&mut ecc);
-                    blocks.push(new_block);
+    fn generate_error_correction(&mut self) {
+        // If you think I'm gonna actually implement my own Reed-Solomon algorithm in this, you're kidding yourself
+        let level = self.ec_level as usize;
+        let block_count = EC_BLOCKS[level][self.version - 1];
+        let ec_per_block = EC_CODEWORDS_PER_BLOCK[level][self.version - 1];
 
-                }
+        // Blocks at the end hold one more data codeword than the ones at the start when the data doesn't divide evenly
+        let total_codewords = QR::raw_modules(self.version) / 8;
+        let short_blocks = block_count - total_codewords % block_count;
+        let short_block_length = total_codewords / block_count - ec_per_block;
 
-                for i in 0..(data_per_block + ec_per_block) {
-                    for block in 0..group_one {
-                        self.payload.push(blocks[block][i]);
-                    }
-                }
+        // Create specified number of EC codewords
+        let enc = Encoder::new(ec_per_block);
 
-            } else {
-                // Get EC codewords only
-                let mut ecc = enc.encode(&self.data).ecc().to_vec();
+        // Split data into required blocks, remembering where each one starts
+        let mut blocks = vec![];
+        let mut offset = 0;
 
-                self.payload.append(&mut self.data);
-                self.payload.append(&mut ecc);
-            }
+        for block in 0..block_count {
+            let length = if block < short_blocks { short_block_length } else { short_block_length + 1 };
+            let data = self.data[offset..offset + length].to_vec();
+            let ecc = enc.encode(&data).ecc().to_vec();
+
+            blocks.push((offset, data, ecc));
+            offset += length;
         }
-        
-        fn create_finder_pattern(&mut self, x: usize, y: usize) {
-            // TODO: Flip x and y names
-            // Any data inserted is represented as 10/11 instead of 0/1
-            // so that masking algorithm knows to skip it
-            for k in 0..7 {
-                for j in 0..7 {
-                    if k == 0 || k == 6 {
-                        self.image[(j + x, k + y)] = 11;
-                    } else if k == 1 || k == 5 {
-                        self.image[(j + x, k + y)] = match j {
-                            0 | 6 => 11,
-                            _ => 10
-                        };
-                    } else {
-                        self.image[(j + x, k + y)] = match j {
-                            1 | 5 => 10,
-                            _ => 11
-                        };
-                    }
+
+        // Start from scratch in case the code is being generated again
+        self.payload = vec![];
+        self.payload_blocks = vec![];
+        self.payload_data = vec![];
+
+        // Interleave the data codewords of every block, then the EC codewords
+        for i in 0..=short_block_length {
+            for (block, (offset, data, _)) in blocks.iter().enumerate() {
+                if i < data.len() {
+                    self.payload.push(data[i]);
+                    self.payload_blocks.push(block);
+                    self.payload_data.push(Some(offset + i));
                 }
             }
         }
 
-        // Helper method to return a bit at offset from a value
-        fn get_bit(offset: usize, value: usize) -> usize {
-            return (value >> offset) & 1;
-        }
-
-        // Places all reserved areas before data is inserted
-        fn place_reserved_areas(&mut self) {
-            // Add finders
-            self.create_finder_pattern(0, 0);
-            self.create_finder_pattern(self.size - 7, 0);
-            self.create_finder_pattern(0, self.size - 7);
-
-            // Add separators and format information areas
-            // Not terribly efficient, but it's clean code
-            for y in 0..self.size {
-                for x in 0..self.size {
-                    // Insert separators 
-                    // Only check 1s, since only the edges of the finder patterns need separators
-                    if self.image[(y, x)] == 11 {
-                        for k in [-1, 1].iter() {
-                            for j in [-1, 1].iter() {
-                                // Get the adjacent squares
-                                let x_offset = (x as isize) + j;
-                                let y_offset = (y as isize) + k;
-
-                                // Ignore negative indexes/outside indexes or there's gonna be P R O B L E M S
-                                if x_offset >= 0 && y_offset >= 0 && x_offset < (self.size as isize) && y_offset < (self.size as isize) {
-                                    let x_i = x_offset as usize;
-                                    let y_i = y_offset as usize;
-                                   
-                                    // If the adjacent square is uninitialized, it needs to be blank
-                                    if self.image[(y_i, x_i)] == 3 {
-                                        self.image[(y_i, x_i)] = 10;
-                                    }
-                                }
-                            } 
-                        }
-                    }
-
-                    // Insert format information areas, represented as 2
-                    // Some parts will be overwritten later, but that's ok
-                    if x == 8 {
-                        if y < 9 || y > (self.size - 9) {
-                            self.image[(y, x)] = 2;
-                        }
-                    } else if y == 8 {
-                        if x < 9 || x > (self.size - 9) {
-                            self.image[(y, x)] = 2;
-                        }
-                    }
-                }
+        for i in 0..ec_per_block {
+            for (block, (_, _, ecc)) in blocks.iter().enumerate() {
+                self.payload.push(ecc[i]);
+                self.payload_blocks.push(block);
+                self.payload_data.push(None);
             }
+        }
+    }
 
-            // Add alignment patterns
-            // Version 1 has none
-            if self.version > 1 {
-                // TODO: Adapt for versions greater than 6
-                let start = self.size - 9;
-                for y in 0..5 {
-                    for x in 0..5 {
-                        if y == 0 || y == 4 {
-                            self.image[(y + start, x + start)] = 11;
-                        } else if y == 1 || y == 3 {
-                            self.image[(y + start, x + start)] = match x {
-                                0 | 4 => 11,
-                                _ => 10
-                            }
-                        } else {
-                            self.image[(y + start, x + start)] = match x {
-                                1 | 3 => 10,
-                                _ => 11
-                            }
-                        }
-                    }
-                }
-            }
+    // Number of damaged codewords each block can lose and still be recovered
+    // The smallest codes reserve a few EC codewords to detect mistakes instead of correcting them
+    pub fn correctable_codewords(&self) -> usize {
+        let level = self.ec_level as usize;
+        let ec_per_block = EC_CODEWORDS_PER_BLOCK[level][self.version - 1];
 
-            // Add vertical timing pattern
-            for y in 8..(self.size - 7) {
-                if y % 2 == 0 {
-                    self.image[(y, 6)] = 11; 
-                } else {
-                    self.image[(y, 6)] = 10;
-                }
-            }
+        let reserved = match (self.version, self.ec_level) {
+            (1, EcLevel::L) => 3,
+            (1, EcLevel::M) | (2, EcLevel::L) => 2,
+            (1, _) | (3, EcLevel::L) => 1,
+            _ => 0,
+        };
+
+        (ec_per_block - reserved) / 2
+    }
+
+    // Returns the codeword and block of the payload that the module at column x, row y holds a bit of, if any
+    pub fn codeword_at(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        self.codewords[(x, y)].map(|(codeword, _)| (codeword, self.payload_blocks[codeword]))
+    }
+
+    // Returns the codeword of the payload and bit of it that the module at column x, row y holds, if any
+    #[cfg(feature = "std")]
+    pub(crate) fn payload_bit_at(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        self.codewords[(x, y)]
+    }
+
+    // Number of codewords in the payload, counting both data and error correction
+    #[cfg(feature = "std")]
+    pub(crate) fn payload_len(&self) -> usize {
+        self.payload.len()
+    }
+
+    // Block that a codeword of the payload belongs to
+    #[cfg(feature = "std")]
+    pub(crate) fn payload_block(&self, codeword: usize) -> usize {
+        self.payload_blocks[codeword]
+    }
+
+    // Number of blocks the payload is split into, and how many EC codewords each has
+    pub(crate) fn blocks(&self) -> (usize, usize) {
+        let level = self.ec_level as usize;
+        (EC_BLOCKS[level][self.version - 1], EC_CODEWORDS_PER_BLOCK[level][self.version - 1])
+    }
+
+    // Returns the data codeword and bit (0 being the least significant) that the module at column x, row y holds,
+    // if it holds data rather than error correction
+    pub fn data_bit_at(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let (codeword, bit) = self.codewords[(x, y)]?;
+        self.payload_data[codeword].map(|data| (data, bit))
+    }
+
+    // Data codewords after the end of the message, which decoders never read and so can be set to anything
+    pub fn padding_codewords(&self) -> Range<usize> {
+        self.message_length..self.data.len()
+    }
+
+    // Sets a bit of a data codeword, which shows up the next time the code is generated
+    pub fn set_data_bit(&mut self, codeword: usize, bit: usize, value: bool) {
+        if value {
+            self.data[codeword] |= 1 << bit;
+        } else {
+            self.data[codeword] &= !(1 << bit);
+        }
+    }
 
-            // Add horizontal timing pattern
-            for x in 8..(self.size - 7) {
-                if x % 2 == 0 {
-                    self.image[(6, x)] = 11; 
+    fn create_finder_pattern(&mut self, left: usize, top: usize) {
+        // Function modules are flagged in the matrix so that data placement and masking know to skip them
+        for y in 0..7 {
+            for x in 0..7 {
+                let dark = if y == 0 || y == 6 {
+                    true
+                } else if y == 1 || y == 5 {
+                    x == 0 || x == 6
                 } else {
-                    self.image[(6, x)] = 10;
-                }
+                    x != 1 && x != 5
+                };
+
+                self.image.set_function(left + x, top + y, dark);
             }
+        }
 
-            // Add dark module
-            self.image[((4 * self.version) + 9, 8)] = 11;
-        }
-
-        /*
-            Places data into the code
-            10/11 represents 0/1 that should not be masked later
-            0/1 represents maskable data
-            2 represents reversed format areas
-            3 represents uninitialized space
-        */
-        fn place_modules(&mut self) {
-            // Fill grid with 3 to represent uninitialized space
-            self.image = RawImage::filled_with(3, self.size, self.size);
-            self.place_reserved_areas();
-
-            // Place data into the code
-            // This took me hours to get working
-            let total_bits = self.payload.len() * 8;
-            let mut bit_index = 0;
-
-            // Start at the bottom-right corner
-            let mut x: isize = self.size as isize - 1;
-            let mut y: isize = self.size as isize - 1;
-
-            // Change in y and x to move zig-zag up and down
-            let mut y_step: isize = -1;
-            let mut x_step: isize = -1; 
-
-            while bit_index < total_bits {
-                // If the area is uninitialized, write the next bit of data
-                if self.image[(y as usize, x as usize)] == 3 {
-                    // The byte is just the bit-index floor division 8
-                    // and then the next bit is 7 - (index % 8)
-                    // Needed since the data is obviously a vector of bytes, not bits
-                    let byte = bit_index / 8;
-                    let bit = 7 - (bit_index % 8);
-
-                    let to_write = QR::get_bit(bit, self.payload[byte] as usize);
-                    self.image[(y as usize, x as usize)] = to_write as u8;
-
-                    bit_index += 1;
-                }
+        // Light separators around the edges that face the rest of the code
+        for i in 0..8 {
+            let column = if left == 0 { 7 } else { left - 1 };
+            let row = if top == 0 { 7 } else { top - 1 };
+            let x = if left == 0 { i } else { left - 1 + i };
+            let y = if top == 0 { i } else { top - 1 + i };
 
-                x += x_step; 
+            self.image.set_function(x, row, false);
+            self.image.set_function(column, y, false);
+        }
+    }
 
-                // Reverse the direction of x every step to zig-zag, and raise y every second placment
-                if x_step == -1 {
-                    x_step = 1;
-                } else {
-                    x_step = -1;
-                    y += y_step;
-                }
+    // Helper method to return a bit at offset from a value
+    fn get_bit(offset: usize, value: usize) -> usize {
+        (value >> offset) & 1
+    }
 
-                // If we're at the top or bottom and x has placed both elements, reverse the y-step
-                if (y == -1 || y == self.size as isize) && x_step == -1 { 
-                    if y_step == -1 {
-                        y_step = 1;
-                        y = 0
-                    } else {
-                        y_step = -1;
-                        y = self.size as isize - 1;
-                    }
-                
-                    // At the vertical timing indicator, we need to skip the column entirely
-                    if x == 8 {
-                        x = 5;
-                    } else {
-                        x -= 2;
-                    }
+    // Every module holding a bit of the format information as (x, y, bit), both copies of it
+    // The first copy runs along row 8 and up column 8 around the top left finder, skipping the timing patterns,
+    // and the second is split between the bottom of column 8 and the right of row 8
+    fn format_modules(size: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+        let row = [0, 1, 2, 3, 4, 5, 7, 8]
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, 8, 14 - i))
+            .chain((0..8).map(move |i| (size - 8 + i, 8, 7 - i)));
+
+        let column = [0, 1, 2, 3, 4, 5, 7]
+            .iter()
+            .enumerate()
+            .map(|(i, &y)| (8, y, i))
+            .chain((0..7).map(move |i| (8, size - 7 + i, 8 + i)));
+
+        row.chain(column)
+    }
+
+    // Places all reserved areas before data is inserted
+    fn place_reserved_areas(&mut self) {
+        // Add finders, along with their separators
+        self.create_finder_pattern(0, 0);
+        self.create_finder_pattern(self.size - 7, 0);
+        self.create_finder_pattern(0, self.size - 7);
+
+        // Reserve the format information areas, which get filled in once the mask is picked
+        for (x, y, _) in QR::format_modules(self.size) {
+            self.image.set_function(x, y, false);
+        }
+
+        // Add alignment patterns
+        // Version 1 has none, and there are none where they would overlap the finders
+        let positions = QR::alignment_positions(self.version);
+        let last = positions.len().saturating_sub(1);
+
+        for (i, &row) in positions.iter().enumerate() {
+            for (j, &column) in positions.iter().enumerate() {
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
                 }
-            }
 
-            // Any leftover space becomes 0
-            for y in 0..self.size {
-                for x in 0..self.size {
-                    if self.image[(y, x)] == 3 {
-                        self.image[(y, x)] = 0;
+                // A dark centre in a light ring in a dark ring
+                for y in 0..5 {
+                    for x in 0..5 {
+                        let ring = (x as isize - 2).abs().max((y as isize - 2).abs());
+                        self.image.set_function(column - 2 + x, row - 2 + y, ring != 1);
                     }
                 }
             }
         }
 
-        // Helper method to copy an Array2D, since it has no copy trait
-        fn copy_image(&self) -> RawImage {
-            let size = self.image.column_len();
-            let mut new_image = RawImage::filled_with(0, size, size);
+        // Add version information, which is only needed from version 7 onwards
+        // The version is followed by a 12 bit BCH error correction code, repeated at the top right and bottom left
+        if self.version >= 7 {
+            let mut remainder = self.version;
 
-            for y in 0..size {
-                for x in 0..size {
-                    new_image[(y, x)] = self.image[(y, x)];
-                }
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
             }
 
-            return new_image;
-        }
-        
-        // Helper method which flips only 0 to 1 and 1 to 0 and ignores all other values
-        fn flip(x: usize, y: usize, image: &mut RawImage) {
-            if image[(y, x)] == 1 {
-                image[(y, x)] = 0; 
-            } else if image[(y, x)] == 0 {
-                image[(y, x)] = 1;
-            }   
-        }
-        
-        // Inserts the format pattern into the masked array of QR codes
-        fn generate_format_pattern(&self, images: &mut Vec<Array2D<u8>>) {
-            // Format strings at Q error for each mask pattern
-            let format_strings = vec![0x355F, 0x3068, 0x3F31, 0x3A06, 0x24B4, 0x2183, 0x2EDA, 0x2BED];
-
-            for i in 0..8 {
-                let format_string = format_strings[i];
-
-                let mut horizontal_bit = 0;
-                let mut vertical_bit = 0;
-
-                // Horizontal format information
-                for x in 0..self.size {
-                    if images[i][(8, x)] == 2 {
-                        if x < self.size / 2 {
-                            images[i][(8, x)] = QR::get_bit(14 - horizontal_bit, format_string) as u8;
-                        } else {
-                            // 7 is repeated on the other side
-                            images[i][(8, x)] = QR::get_bit(15 - horizontal_bit, format_string) as u8;
-                        }
-
-                        horizontal_bit += 1;
-                    }
-                }
+            let version_bits = (self.version << 12) | remainder;
 
-                // Vertical format information
-                for y in 0..self.size {
-                    if images[i][(y, 8)] == 2 {
-                        images[i][(y, 8)] = QR::get_bit(vertical_bit, format_string) as u8;
+            for i in 0..18 {
+                let dark = QR::get_bit(i, version_bits) == 1;
+                let a = self.size - 11 + i % 3;
+                let b = i / 3;
 
-                        // Skip 7 as it's already placed
-                        if vertical_bit == 7 {
-                            vertical_bit += 1;
-                        }
-
-                        vertical_bit += 1;
-                    }
-                }
+                self.image.set_function(a, b, dark);
+                self.image.set_function(b, a, dark);
             }
         }
 
-        // Evaluates each mask against the 4 test criteria and returns the index of the best one
-        fn evaluate_masks(&self, masked: &Vec<RawImage>) -> usize {
-            // Vector to store total penalty for each mask
-            let mut penalties = vec![0; 8];
-
-            for (i, mask) in masked.iter().enumerate() {
-                // Evaluation 1: Run lengths of same color of 5 or higher
-                let mut current_color = 2;
-                let mut run_length = 0;
-
-                // Horizontal runs
-                for y in 0..self.size {
-                    for x in 0..self.size {
-                        if current_color != mask[(y, x)] {
-                            current_color = mask[(y, x)];
-                            
-                            if run_length >= 5 {
-                                penalties[i] += 3 + (run_length - 5);
-                            }
-                            
-                            run_length = 0;
-                        } 
-                        
-                        run_length += 1;
-                    }
+        // Add timing patterns, which alternate between dark and light along row and column 6
+        for i in 8..(self.size - 7) {
+            self.image.set_function(6, i, i % 2 == 0);
+            self.image.set_function(i, 6, i % 2 == 0);
+        }
 
-                    // Write any remaining run length penalties that happen on the edge
-                    if run_length >= 5 {
-                        penalties[i] += 3 + (run_length - 5);
-                    }
+        // Add dark module
+        self.image.set_function(8, (4 * self.version) + 9, true);
+    }
 
-                    current_color = 2;
-                    run_length = 0;
-                }
+    // Places data into every module that isn't part of a function pattern
+    // Any modules left over once the payload runs out stay light
+    fn place_modules(&mut self) {
+        self.image = Matrix::new(self.size);
+        self.place_reserved_areas();
+
+        // Place data into the code
+        // This took me hours to get working
+        let total_bits = self.payload.len() * 8;
+        let mut bit_index = 0;
+
+        // Start at the bottom-right corner
+        let mut x: isize = self.size as isize - 1;
+        let mut y: isize = self.size as isize - 1;
+
+        // Change in y and x to move zig-zag up and down
+        let mut y_step: isize = -1;
+        let mut x_step: isize = -1; 
+
+        while bit_index < total_bits {
+            // If the module is free, write the next bit of data
+            if !self.image.is_function(x as usize, y as usize) {
+                // The byte is just the bit-index floor division 8
+                // and then the next bit is 7 - (index % 8)
+                // Needed since the data is obviously a vector of bytes, not bits
+                let byte = bit_index / 8;
+                let bit = 7 - (bit_index % 8);
+
+                let to_write = QR::get_bit(bit, self.payload[byte] as usize);
+                self.image.set(x as usize, y as usize, to_write == 1);
+                self.codewords[(x as usize, y as usize)] = Some((byte, bit));
+
+                bit_index += 1;
+            }
 
-                // Vertical runs
-                for x in 0..self.size {
-                    for y in 0..self.size {
-                        if current_color != mask[(y, x)] {
-                            current_color = mask[(y, x)];
-                            
-                            if run_length >= 5 {
-                                penalties[i] += 3 + (run_length - 5);
-                            }
-                            
-                            run_length = 0;
-                        } 
-                        
-                        run_length += 1;
-                    }
+            x += x_step; 
 
-                    // Write any remaining run length penalties that happen on the edge
-                    if run_length >= 5 {
-                        penalties[i] += 3 + (run_length - 5);
-                    }
+            // Reverse the direction of x every step to zig-zag, and raise y every second placment
+            if x_step == -1 {
+                x_step = 1;
+            } else {
+                x_step = -1;
+                y += y_step;
+            }
 
-                    current_color = 2;
-                    run_length = 0;
+            // If we're at the top or bottom and x has placed both elements, reverse the y-step
+            if (y == -1 || y == self.size as isize) && x_step == -1 { 
+                if y_step == -1 {
+                    y_step = 1;
+                    y = 0
+                } else {
+                    y_step = -1;
+                    y = self.size as isize - 1;
+                }
+            
+                // At the vertical timing indicator, we need to skip the column entirely
+                if x == 8 {
+                    x = 5;
+                } else {
+                    x -= 2;
                 }
+            }
+        }
+    }
 
-                // Evaluation 2: 2x2 blocks of the same color
-                for y in 0..(self.size - 1) {
-                    for x in 0..(self.size - 1) {
-                        let square = vec![mask[(y, x)], mask[(y + 1, x)], mask[(y, x + 1)], mask[(y + 1, x + 1)]];
+    // The 15 bit format information for an EC level and mask
+    pub(crate) fn format_string(ec_level: EcLevel, mask: usize) -> usize {
+        // The format is the EC level and mask, followed by a 10 bit BCH error correction code
+        let format_data = (ec_level.format_bits() << 3) | mask;
+        let mut remainder = format_data;
 
-                        // If square contains all same color
-                        if square.iter().all(|&item| item == mask[(y, x)]) {
-                            penalties[i] += 3;
-                        }
-                    }
-                }
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+
+        // The whole thing is XORed with a fixed pattern so it can never be all zeroes
+        ((format_data << 10) | remainder) ^ 0x5412
+    }
 
-                // Evaluation 3: Check for a specific pattern appearing either horizontally or vertically
-                // Horizontal
-                for y in 0..self.size {
-                    for x in 0..(self.size - 10) {
-                        let pattern_a = vec![1, 0, 1, 1, 1, 0, 1, 0, 0, 0, 0];
-                        let pattern_b = vec![0, 0, 0, 0, 1, 0, 1, 1, 1, 0, 1]; 
+    // Inserts the format pattern into each of the masked codes, the index of which is its mask
+    fn generate_format_pattern(&self, images: &mut [Matrix]) {
+        for (i, image) in images.iter_mut().enumerate() {
+            let format_string = QR::format_string(self.ec_level, i);
 
-                        let test_pattern = (0..11).map(|i| mask[(y, x + i)]).collect::<Vec<u8>>();
+            for (x, y, bit) in QR::format_modules(self.size) {
+                image.set_function(x, y, QR::get_bit(bit, format_string) == 1);
+            }
+        }
+    }
 
-                        if test_pattern == pattern_a || test_pattern == pattern_b {
-                            penalties[i] += 40;
-                        }
-                    }
-                }
+    // Scores a masked code against the 4 penalty rules, where lower is better
+    fn penalty(&self, mask: &Matrix) -> usize {
+        let lines = (0..self.size)
+            .map(|y| (0..self.size).map(|x| mask.is_dark(x, y)).collect::<Vec<_>>())
+            .chain((0..self.size).map(|x| (0..self.size).map(|y| mask.is_dark(x, y)).collect::<Vec<_>>()));
 
-                // Vertical
-                for x in 0..self.size {
-                    for y in 0..(self.size - 10) {
-                        let pattern_a = vec![1, 0, 1, 1, 1, 0, 1, 0, 0, 0, 0];
-                        let pattern_b = vec![0, 0, 0, 0, 1, 0, 1, 1, 1, 0, 1]; 
+        let mut penalty = 0;
 
-                        let test_pattern = (0..11).map(|i| mask[(y + i, x)]).collect::<Vec<u8>>();
+        for line in lines {
+            // Runs of the same color as (dark, length), starting afresh on every row and column
+            let mut runs: Vec<(bool, usize)> = vec![];
 
-                        if test_pattern == pattern_a || test_pattern == pattern_b {
-                            penalties[i] += 40;
-                        }
-                    }
+            for &dark in line.iter() {
+                match runs.last_mut() {
+                    Some((color, length)) if *color == dark => *length += 1,
+                    _ => runs.push((dark, 1)),
                 }
+            }
 
-                // Evaluation 4: Ratio of dark to light modules
-                let total_modules = self.size * self.size;
-                let mut dark_modules: isize = 0;
+            // Rule 1: 3 points for each run of 5 of the same color, and 1 more for every module past 5
+            penalty += runs.iter().filter(|(_, length)| *length >= 5).map(|(_, length)| length - 2).sum::<usize>();
 
-                for row in mask.rows_iter() {
-                    for module in row {
-                        dark_modules += *module as isize;
-                    }
-                }
+            // Rule 3: 40 points for each finder-like pattern
+            penalty += 40 * match self.penalty_rules {
+                PenaltyRules::Iso2015 => QR::finder_like_runs(&runs, self.size),
+                PenaltyRules::Iso2006 => QR::finder_like_modules(&line),
+            };
+        }
+
+        // Rule 2: 3 points for each 2x2 block of the same color, which can overlap
+        for y in 0..(self.size - 1) {
+            for x in 0..(self.size - 1) {
+                let dark = mask.is_dark(x, y);
 
-                let percentage_dark = ((dark_modules as f32 / total_modules as f32) * 100.0) as isize;
-                let previous_multiple = percentage_dark - (percentage_dark % 5);
-                let next_multiple = percentage_dark + (5 - (percentage_dark % 5));
+                let block = [mask.is_dark(x + 1, y), mask.is_dark(x, y + 1), mask.is_dark(x + 1, y + 1)];
 
-                let multiples = vec![previous_multiple, next_multiple];
-                let subtracted = multiples.iter().map(|&x| isize::abs(x - 50)).collect::<Vec<_>>();
-                let divided = subtracted.iter().map(|&x| x / 5).collect::<Vec<_>>();
-                penalties[i] += divided.into_iter().min().unwrap() * 10;
+                if block.iter().all(|&module| module == dark) {
+                    penalty += 3;
+                }
             }
+        }
 
-            // The best mask is the one with the lowest score
-            let best_code_index: usize = penalties
-                .iter()
-                .enumerate()
-                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-                .map(|(index, _)| index).unwrap();
-            
-            println!("Best mask is mask {} with penalty {}", best_code_index, penalties[best_code_index]);
+        // Rule 4: 10 points for every 5% the proportion of dark modules is away from 45-55%
+        // k is the smallest whole number with (45 - 5k)% <= dark <= (55 + 5k)%, worked out without rounding
+        let total = self.size * self.size;
+        let deviation = (mask.dark_count() * 20).abs_diff(total * 10);
+        penalty += (deviation.div_ceil(total).max(1) - 1) * 10;
 
-            return best_code_index;
+        penalty
+    }
+
+    // Counts 1:1:3:1:1 (dark:light:dark:light:dark) patterns with a light area 4 units of the ratio wide on either
+    // side, as ISO/IEC 18004:2015 has it
+    // The ratio holds at any scale, and the quiet zone counts as light, so patterns can run into the edge
+    // A pattern with light areas on both sides counts once for each
+    fn finder_like_runs(runs: &[(bool, usize)], size: usize) -> usize {
+        // Pad the line with the quiet zone, which is as wide as the symbol so it always has room for the light area
+        let mut padded = vec![(false, size)];
+
+        for &(dark, length) in runs {
+            match padded.last_mut() {
+                Some((color, total)) if *color == dark => *total += length,
+                _ => padded.push((dark, length)),
+            }
         }
 
-        // Masks the QR code, evaluating each version. Also inserts the format pattern
-        fn mask_and_format(&mut self) {
-            // Generate 8 maskable copies to evaluate each mask
-            let mut masked = (0..8).map(|_| self.copy_image()).collect::<Vec<RawImage>>();
+        match padded.last_mut() {
+            Some((false, total)) => *total += size,
+            _ => padded.push((false, size)),
+        }
 
-            // Generate the 8 different masks, and revert 10/11 to 0/1
-            for y in 0..self.size {
-                for x in 0..self.size {
-                    // Mask 0
-                    if (x + y) % 2 == 0 {
-                        QR::flip(x, y, &mut masked[0])
-                    }
+        padded
+            .windows(7)
+            .filter(|window| !window[0].0)
+            .map(|window| {
+                let lengths: Vec<usize> = window.iter().map(|&(_, length)| length).collect();
+                let n = lengths[1];
+                let core = lengths[2] == n && lengths[3] == n * 3 && lengths[4] == n && lengths[5] == n;
+
+                (core && lengths[0] >= n * 4 && lengths[6] >= n) as usize
+                    + (core && lengths[6] >= n * 4 && lengths[0] >= n) as usize
+            })
+            .sum()
+    }
 
-                    // Mask 1
-                    if y % 2 == 0 {
-                        QR::flip(x, y, &mut masked[1]);
-                    }
+    // Counts 1011101 patterns with 4 light modules right before or after them, as ISO/IEC 18004:2006 has it
+    // Only modules inside the symbol count, and a pattern with light on both sides counts twice
+    fn finder_like_modules(line: &[bool]) -> usize {
+        const BEFORE: [bool; 11] = [false, false, false, false, true, false, true, true, true, false, true];
+        const AFTER: [bool; 11] = [true, false, true, true, true, false, true, false, false, false, false];
 
-                    // Mask 2
-                    if x % 3 == 0 {
-                        QR::flip(x, y, &mut masked[2]);
-                    }
+        line.windows(11).filter(|&window| window == BEFORE || window == AFTER).count()
+    }
 
-                    // Mask 3
-                    if (x + y) % 3 == 0 {
-                        QR::flip(x, y, &mut masked[3]);
-                    }
+    // The code with each of the 8 masks applied, along with the format information that goes with it
+    fn masked_copies(&self) -> Vec<Matrix> {
+        let mut masked = vec![self.image.clone(); 8];
 
-                    // Mask 4
-                    if ((y / 2) + (x / 3)) % 2 == 0 {
-                        QR::flip(x, y, &mut masked[4]);
+        // Masks leave function modules alone
+        for y in 0..self.size {
+            for x in 0..self.size {
+                for (i, mask) in masked.iter_mut().enumerate() {
+                    if QR::mask_applies(i, x, y) {
+                        mask.flip(x, y);
                     }
+                }
+            }
+        }
 
-                    // Mask 5
-                    if ((x * y) % 2) + ((x * y) % 3) == 0 {
-                        QR::flip(x, y, &mut masked[5]);
-                    }
+        // Format patterns have to be inserted now, as they are part of the mask evaluation
+        self.generate_format_pattern(&mut masked);
+        masked
+    }
 
-                    // Mask 6
-                    if (((x * y) % 2) + ((x * y) % 3)) % 2 == 0 {
-                        QR::flip(x, y, &mut masked[6]);
-                    }
+    // Penalty of each of the 8 masks for the generated code, by the rules set with set_penalty_rules
+    pub fn mask_penalties(&self) -> [usize; 8] {
+        let mut penalties = [0; 8];
 
-                    // Mask 7
-                    if (((x + y) % 2) + ((x * y) % 3)) % 2 == 0 {
-                        QR::flip(x, y, &mut masked[7]);
-                    }
+        for (penalty, mask) in penalties.iter_mut().zip(self.masked_copies().iter()) {
+            *penalty = self.penalty(mask);
+        }
 
-                    // Revert 10/11 to 0/1 for each mask
-                    for i in 0..8 {
-                        if masked[i][(y, x)] == 10 {
-                            masked[i][(y, x)] = 0;
-                        } else if masked[i][(y, x)] == 11 {
-                            masked[i][(y, x)] = 1;
-                        }
-                    }
-                }
-            }
+        penalties
+    }
 
-            // Format patterns have to be inserted now, as they are part of the mask evaluation
-            self.generate_format_pattern(&mut masked);
+    // Masks the QR code, evaluating each version. Also inserts the format pattern
+    fn mask_and_format(&mut self) {
+        let mut masked = self.masked_copies();
 
-            // Evaluate all the masks
-            let best = self.evaluate_masks(&masked);
-            
-            // Copy the best mask to self.masked
-            for y in 0..self.size {
-                for x in 0..self.size {
-                    self.masked[(y, x)] = masked[best][(y, x)];
-                }
+        // Pick the mask with the lowest penalty, or the first of them if there's a tie, unless one was picked already
+        let best = match self.forced_mask {
+            Some(mask) => mask,
+            None => {
+                let penalties: Vec<usize> = masked.iter().map(|mask| self.penalty(mask)).collect();
+                (0..8).min_by_key(|&i| penalties[i]).unwrap_or(0)
             }
-        }
+        };
 
-        // Prints the QR code to terminal
-        fn print_qr(&self, image: &RawImage) {
-            println!("\n");
+        self.mask = best;
+        self.masked = masked.swap_remove(best);
+    }
 
-            for row_iter in image.rows_iter() {
-                print!("    ");
+    // Returns true if the mask pattern flips the module at column x, row y
+    // The conditions are written the same way as in the spec
+    // There are only masks 0 to 7, so anything else panics rather than quietly drawing the wrong pattern
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn mask_applies(mask: usize, x: usize, y: usize) -> bool {
+        match mask {
+            0 => (x + y) % 2 == 0,
+            1 => y % 2 == 0,
+            2 => x % 3 == 0,
+            3 => (x + y) % 3 == 0,
+            4 => ((y / 2) + (x / 3)) % 2 == 0,
+            5 => ((x * y) % 2) + ((x * y) % 3) == 0,
+            6 => (((x * y) % 2) + ((x * y) % 3)) % 2 == 0,
+            7 => (((x + y) % 2) + ((x * y) % 3)) % 2 == 0,
+            _ => unreachable!("mask {} isn't between 0 and 7", mask),
+        }
+    }
 
-                for module in row_iter {
-                    if *module == 1 || *module == 11 {
-                        print!("██");
-                    } else if *module == 3 {
-                        print!("..");
-                    } else if *module == 2 {
-                        print!("FF");
-                    } else {
-                        print!("  ");
-                    }
-                }
+    pub fn generate(&mut self) {
+        self.generate_error_correction();
+        self.place_modules();
+        self.mask_and_format();
+    }
 
-                println!("    ");
-            }
+    // Sets which edition of the standard masks are scored by, which takes effect the next time the code is generated
+    pub fn set_penalty_rules(&mut self, rules: PenaltyRules) {
+        self.penalty_rules = rules;
+    }
 
-            println!("\n");
+    // Generates the code with a specific mask pattern (0-7) instead of the one with the lowest penalty
+    pub fn generate_with_mask(&mut self, mask: usize) -> Result<(), Error> {
+        if mask > 7 {
+            return Err(Error::InvalidField {
+                field: "mask",
+                reason: format!("{} isn't between 0 and 7", mask),
+            });
         }
 
-        pub fn generate(&mut self) {
-            self.generate_error_correction();
-            self.place_modules();
-            self.mask_and_format();
-            self.print_qr(&self.masked);
+        self.forced_mask = Some(mask);
+        self.generate();
+        Ok(())
+    }
+
+    // Describes the code, along with every module if modules is set
+    pub fn metadata(&self, modules: bool) -> Metadata {
+        let segments: Vec<SegmentMetadata> = self
+            .segments
+            .iter()
+            .map(|segment| SegmentMetadata {
+                mode: segment.mode,
+                characters: segment.data.len(),
+                bits: segment.bits(self.version).unwrap_or(0),
+            })
+            .collect();
+
+        let (blocks, ec_per_block) = self.blocks();
+        let data_bits = segments.iter().map(|segment| segment.bits).sum();
+        let capacity_bits = QR::data_codewords(self.version, self.ec_level) * 8;
+
+        Metadata {
+            version: self.version,
+            ec_level: self.ec_level,
+            mask: self.mask,
+            size: self.size,
+            segments,
+            data_bits,
+            capacity_bits,
+            remaining_bits: capacity_bits - data_bits,
+            data_codewords: capacity_bits / 8,
+            ec_codewords: blocks * ec_per_block,
+            modules: if modules {
+                Some((0..self.size).map(|y| (0..self.size).map(|x| self.is_dark(x, y)).collect()).collect())
+            } else {
+                None
+            },
         }
+    }
 
-        pub fn save_image(&self, path: String, size: u32) {
-            // Add quiet zone of 4 pixels around the code
-            let mut imgbuf = image::GrayImage::new(self.size as u32 + 8, self.size as u32 + 8);
+    // Returns true if the module at column x, row y is dark
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.masked.is_dark(x, y)
+    }
 
-            for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-                // Only write from the code if we're in range of the code or else we're gonna overrun
-                // and there will be P R O B L E M S
-                if x > 3 && (x as usize) < self.size + 4 && y > 3 && (y as usize) < self.size + 4 { 
-                    *pixel = image::Luma([(1 - self.masked[(y as usize - 4, x as usize - 4)]) * 255]);
-                } else {
-                    *pixel = image::Luma([255u8]);
-                }
-            }
+    // Returns true if the module at column x, row y is part of a function pattern rather than data
+    pub fn is_function(&self, x: usize, y: usize) -> bool {
+        self.image.is_function(x, y)
+    }
 
-            // Resize the image since 30x30 pixel images are apparently "not high enough resolution" now
-            // Use nearest-neighbor so it actually looks good
-            let resized = image::imageops::resize(&imgbuf, size, size, image::imageops::FilterType::Nearest);
-            resized.save(&path).unwrap();
-            println!("Saved to {}", path);
-        }
+    // Top-left corners of the three finder patterns as (x, y)
+    pub fn finder_positions(&self) -> [(usize, usize); 3] {
+        [(0, 0), (self.size - 7, 0), (0, self.size - 7)]
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save_image(&self, path: &str, options: &RenderOptions) -> image::ImageResult<()> {
+        render::save(self, path, options)
     }
-}
\ No newline at end of file
+}
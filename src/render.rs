@@ -0,0 +1,576 @@
+pub mod halftone;
+pub mod logo;
+pub mod rgb;
+pub mod shape;
+pub mod svg;
+pub mod swiss;
+
+use crate::error::Error;
+use crate::qr::{EcLevel, QR};
+use crate::segment::Segment;
+use image::imageops::{self, FilterType};
+use image::error::{ImageError, LimitError, LimitErrorKind, ParameterError, ParameterErrorKind};
+use image::{DynamicImage, GrayImage, ImageOutputFormat, ImageResult, Rgba, RgbaImage};
+use logo::Logo;
+use shape::{FinderShape, ModuleShape};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// Narrowest quiet zone the spec allows, in modules
+// Micro QR only needs 2, but only regular codes are generated
+pub const MIN_QUIET_ZONE: u32 = 4;
+
+// Lowest contrast ratio between the dark and light colors before scanning gets unreliable
+// This is the WCAG minimum for graphics, which is about where phone cameras start to struggle
+pub const MIN_CONTRAST: f64 = 3.0;
+
+// Widest image that gets drawn, in pixels, which is already 1 GiB of RGBA
+// Scales are only limited by u32 otherwise, and a big enough one takes down the process trying to allocate it
+pub const MAX_IMAGE_SIZE: u32 = 16384;
+
+pub const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+pub const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+pub const TRANSPARENT: Rgba<u8> = Rgba([255, 255, 255, 0]);
+
+// How big each module is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scale {
+    // Fixed number of pixels per module
+    PerModule(u32),
+    // Largest whole number of pixels per module that fits in a square image of this size,
+    // with any leftover pixels added to the quiet zone
+    Fit(u32),
+}
+
+// Parses either the image size in pixels, or pixels per module with an x suffix (e.g. 10x)
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Scale, String> {
+        match text.strip_suffix('x') {
+            Some(scale) => scale.parse().map(Scale::PerModule),
+            None => text.parse().map(Scale::Fit),
+        }
+        .map_err(|_| format!("{} isn't a size like 1000 or 10x", text))
+    }
+}
+
+#[derive(Clone)]
+pub struct RenderOptions {
+    pub scale: Scale,
+    // Resolution written to the pHYs chunk of PNG files, so printouts come out at the intended size
+    pub dpi: Option<u32>,
+    // Width of the light border around the code in modules, used by every renderer
+    pub quiet_zone: u32,
+    // Colors of the dark and light modules, the light color also fills the quiet zone
+    pub dark: Rgba<u8>,
+    pub light: Rgba<u8>,
+    // Shapes of the data modules and of the finder patterns' outer ring and inner eye
+    pub module_shape: ModuleShape,
+    pub finder_ring: FinderShape,
+    pub finder_eye: FinderShape,
+    // Picture or blank space in the middle of the code
+    pub logo: Option<Logo>,
+    // Picture the modules are split up to show, for codes made with halftone::fit
+    pub halftone: Option<GrayImage>,
+    // Draws the Swiss cross over the middle of the code, which QR-bills have to have
+    pub swiss_cross: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            scale: Scale::PerModule(8),
+            dpi: None,
+            quiet_zone: MIN_QUIET_ZONE,
+            dark: BLACK,
+            light: WHITE,
+            module_shape: ModuleShape::Square,
+            finder_ring: FinderShape::Square,
+            finder_eye: FinderShape::Square,
+            logo: None,
+            halftone: None,
+            swiss_cross: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    // Lists anything in the options that could stop the code from scanning
+    pub fn warnings(&self) -> Vec<Warning> {
+        let mut warnings = vec![];
+
+        if self.quiet_zone < MIN_QUIET_ZONE {
+            warnings.push(Warning::NarrowQuietZone(self.quiet_zone));
+        }
+
+        let dark = luminance(self.dark);
+        let light = luminance(self.light);
+
+        // Lots of scanners only look for dark modules on a light background
+        if dark > light {
+            warnings.push(Warning::ReversedColors);
+        }
+
+        let contrast = (dark.max(light) + 0.05) / (dark.min(light) + 0.05);
+
+        if contrast < MIN_CONTRAST {
+            warnings.push(Warning::LowContrast(contrast));
+        }
+
+        warnings
+    }
+}
+
+// Options that still produce a code, but one that some scanners may struggle with
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    NarrowQuietZone(u32),
+    ReversedColors,
+    LowContrast(f64),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::NarrowQuietZone(width) => write!(
+                f,
+                "quiet zone of {} modules is below the minimum of {}, the code may not scan",
+                width, MIN_QUIET_ZONE
+            ),
+            Warning::ReversedColors => write!(
+                f,
+                "dark modules are lighter than light modules, many scanners can't read inverted codes"
+            ),
+            Warning::LowContrast(contrast) => write!(
+                f,
+                "contrast ratio of {:.1}:1 is below the minimum of {}:1, the code may not scan",
+                contrast, MIN_CONTRAST
+            ),
+        }
+    }
+}
+
+// Relative luminance of a color as defined by WCAG, from 0 for black to 1 for white
+// Anything see-through is measured as it would look on white paper
+fn luminance(color: Rgba<u8>) -> f64 {
+    let Rgba([red, green, blue, alpha]) = color;
+    let alpha = alpha as f64 / 255.0;
+
+    let linear = |channel: u8| {
+        let channel = (channel as f64 * alpha + 255.0 * (1.0 - alpha)) / 255.0;
+
+        if channel <= 0.03928 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.2126 * linear(red) + 0.7152 * linear(green) + 0.0722 * linear(blue)
+}
+
+// Parses a hex color such as #1a2b3c or #1a2b3c80, with or without the #
+// "transparent" is accepted as a shorthand for a fully transparent color
+pub fn parse_color(text: &str) -> Option<Rgba<u8>> {
+    if text.eq_ignore_ascii_case("transparent") {
+        return Some(TRANSPARENT);
+    }
+
+    let hex = text.strip_prefix('#').unwrap_or(text);
+
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut channels = [255u8; 4];
+
+    for (i, channel) in channels.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(Rgba(channels))
+}
+
+// Generates a code that suits the options, fitting it around the logo or halftone picture if there is one
+// Logos and halftones pick their own version, and halftones their own mask, so those can't be given for them
+pub fn fit(
+    segments: Vec<Segment>,
+    ec_level: EcLevel,
+    version: Option<usize>,
+    mask: Option<usize>,
+    bump_ec: bool,
+    options: &RenderOptions,
+) -> Result<QR, Error> {
+    let picked = |field: &'static str| Error::InvalidField {
+        field,
+        reason: "can't be picked for codes with a logo or halftone".to_string(),
+    };
+
+    let code = match (&options.logo, &options.halftone) {
+        (_, Some(_)) if mask.is_some() => Err(picked("mask")),
+        (Some(_), _) | (_, Some(_)) if version.is_some() => Err(picked("version")),
+        (_, Some(picture)) => halftone::fit(segments, ec_level, picture),
+        (Some(logo), None) => {
+            let mut code = logo::fit(segments, ec_level, logo, bump_ec)?;
+
+            if let Some(mask) = mask {
+                code.generate_with_mask(mask)?;
+            }
+
+            Ok(code)
+        }
+        (None, None) => {
+            let mut code = match version {
+                Some(version) => QR::from_segments(&segments, ec_level, version)?,
+                None => QR::with_segments(segments, ec_level)?,
+            };
+
+            match mask {
+                Some(mask) => code.generate_with_mask(mask)?,
+                None => code.generate(),
+            }
+
+            Ok(code)
+        }
+    }?;
+
+    // Turn down scales that are too big to draw now, rather than when the code gets saved
+    image_size(&code, options)?;
+    Ok(code)
+}
+
+// Width and height of the image the code is drawn in, in pixels
+pub fn image_size(code: &QR, options: &RenderOptions) -> Result<u32, Error> {
+    let step = if options.halftone.is_some() { halftone::CELLS as u32 } else { 1 };
+    layout(code, options, step).map(|(_, image_size, _)| image_size)
+}
+
+// Works out the pixels per module, the size of the image and where the code starts in it
+// The pixels per module is kept to a multiple of step, for renderers that split modules up further
+// Everything is worked out in u64 so that any quiet zone and scale can be checked against MAX_IMAGE_SIZE
+fn layout(code: &QR, options: &RenderOptions, step: u32) -> Result<(u32, u32, u32), Error> {
+    let quiet_zone = options.quiet_zone as u64;
+    let modules = code.size as u64 + quiet_zone * 2;
+    let step = step as u64;
+
+    let (scale, image_size) = match options.scale {
+        Scale::PerModule(scale) => {
+            let scale = (scale as u64 / step * step).max(step);
+            (scale, modules.checked_mul(scale))
+        }
+        Scale::Fit(size) => {
+            // Never go below step pixels per module, even if that overshoots the requested size
+            let scale = (size as u64 / modules / step * step).max(step);
+            (scale, modules.checked_mul(scale).map(|drawn| drawn.max(size as u64)))
+        }
+    };
+
+    let image_size = image_size
+        .filter(|&image_size| image_size <= MAX_IMAGE_SIZE as u64)
+        .ok_or(Error::ImageTooLarge { max: MAX_IMAGE_SIZE })?;
+
+    // Split the leftover pixels evenly on both sides so the code stays centred
+    let offset = (image_size - modules * scale) / 2 + quiet_zone * scale;
+
+    // All of them are within MAX_IMAGE_SIZE now
+    Ok((scale as u32, image_size as u32, offset as u32))
+}
+
+// Image errors are all the writers can return, so an image too big to draw becomes a limit error
+// and anything else keeps its message as a parameter error
+fn image_error(error: Error) -> ImageError {
+    match error {
+        Error::ImageTooLarge { .. } => ImageError::Limits(LimitError::from_kind(LimitErrorKind::DimensionError)),
+        error => ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(error.to_string()))),
+    }
+}
+
+// Draws the code with every module the same whole number of pixels wide
+pub fn render(code: &QR, options: &RenderOptions) -> Result<RgbaImage, Error> {
+    if let Some(picture) = &options.halftone {
+        return halftone::render(code, picture, options);
+    }
+
+    let (scale, image_size, offset) = layout(code, options, 1)?;
+    let offset = offset as f64;
+    let scale = scale as f64;
+
+    let mut image = RgbaImage::from_pixel(image_size, image_size, options.light);
+
+    if let Some(logo) = &options.logo {
+        let area = logo.area(code);
+        let left = offset + area.x as f64 * scale;
+        let top = offset + area.y as f64 * scale;
+        let width = area.width as f64 * scale;
+        let height = area.height as f64 * scale;
+
+        if let Some(picture) = &logo.image {
+            // Shrink the picture to fit the area without stretching it, then centre it
+            let fit = (width / picture.width() as f64).min(height / picture.height() as f64);
+            let fitted_width = (picture.width() as f64 * fit).round().max(1.0) as u32;
+            let fitted_height = (picture.height() as f64 * fit).round().max(1.0) as u32;
+            let fitted = imageops::resize(picture, fitted_width, fitted_height, FilterType::Lanczos3);
+
+            let x = left + (width - fitted_width as f64) / 2.0;
+            let y = top + (height - fitted_height as f64) / 2.0;
+            imageops::overlay(&mut image, &fitted, x.round() as u32, y.round() as u32);
+        }
+
+        // Light function modules get painted back over the picture, the dark ones are drawn with everything else
+        for (x, y) in logo.function_modules(code) {
+            for pixel_y in 0..scale as u32 {
+                for pixel_x in 0..scale as u32 {
+                    let pixel_x = (offset + x as f64 * scale) as u32 + pixel_x;
+                    let pixel_y = (offset + y as f64 * scale) as u32 + pixel_y;
+                    image.put_pixel(pixel_x, pixel_y, options.light);
+                }
+            }
+        }
+    }
+
+    for piece in shape::pieces(code, options) {
+        let tile = piece.outer;
+
+        // Only look at the pixels under the piece, checking whether the centre of each one is covered
+        let left = (offset + tile.x * scale).floor() as u32;
+        let top = (offset + tile.y * scale).floor() as u32;
+        let right = ((offset + (tile.x + tile.size) * scale).ceil() as u32).min(image_size);
+        let bottom = ((offset + (tile.y + tile.size) * scale).ceil() as u32).min(image_size);
+
+        for y in top..bottom {
+            for x in left..right {
+                let module_x = (x as f64 + 0.5 - offset) / scale;
+                let module_y = (y as f64 + 0.5 - offset) / scale;
+
+                if piece.contains(module_x, module_y) {
+                    image.put_pixel(x, y, options.dark);
+                }
+            }
+        }
+    }
+
+    if options.swiss_cross {
+        for rect in swiss::cross(code) {
+            let color = if rect.dark { options.dark } else { options.light };
+            let left = (offset + rect.x * scale).floor() as u32;
+            let top = (offset + rect.y * scale).floor() as u32;
+            let right = (offset + (rect.x + rect.width) * scale).ceil() as u32;
+            let bottom = (offset + (rect.y + rect.height) * scale).ceil() as u32;
+
+            for y in top..bottom {
+                for x in left..right {
+                    let module_x = (x as f64 + 0.5 - offset) / scale;
+                    let module_y = (y as f64 + 0.5 - offset) / scale;
+
+                    if rect.contains(module_x, module_y) {
+                        image.put_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+// Draws the code as text for printing to the terminal
+// Each module is two characters wide so that it comes out roughly square
+pub fn terminal(code: &QR, options: &RenderOptions) -> String {
+    let quiet_zone = options.quiet_zone as usize;
+    let blank_line = " ".repeat((code.size + quiet_zone * 2) * 2);
+    let border = "  ".repeat(quiet_zone);
+
+    let mut lines = vec![blank_line.clone(); quiet_zone];
+
+    for y in 0..code.size {
+        let mut line = border.clone();
+
+        for x in 0..code.size {
+            line.push_str(if code.is_dark(x, y) { "██" } else { "  " });
+        }
+
+        line.push_str(&border);
+        lines.push(line);
+    }
+
+    lines.extend(vec![blank_line; quiet_zone]);
+    lines.join("\n")
+}
+
+// Draws the code as text using half blocks, so each character holds two modules stacked on top of each other
+// That's half the height of terminal, which is small enough to fit a whole code on screen over SSH
+pub fn terminal_compact(code: &QR, options: &RenderOptions) -> String {
+    let quiet_zone = options.quiet_zone as usize;
+    let width = code.size + quiet_zone * 2;
+
+    // Anything outside of the code is quiet zone, so it's light
+    let is_dark = |x: usize, y: usize| {
+        x >= quiet_zone
+            && y >= quiet_zone
+            && x < quiet_zone + code.size
+            && y < quiet_zone + code.size
+            && code.is_dark(x - quiet_zone, y - quiet_zone)
+    };
+
+    (0..width)
+        .step_by(2)
+        .map(|y| {
+            (0..width)
+                .map(|x| match (is_dark(x, y), is_dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// What a code can be written out as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Png,
+    Svg,
+    Jpeg,
+    Bmp,
+    Gif,
+    // Text drawn with full blocks, or half blocks for the compact version
+    Terminal,
+    Compact,
+}
+
+impl Format {
+    // The format a file extension stands for, if it's one of these
+    pub fn from_path(path: &str) -> Option<Format> {
+        let extension = Path::new(path).extension()?.to_string_lossy().to_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(Format::Png),
+            "svg" => Some(Format::Svg),
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "bmp" => Some(Format::Bmp),
+            "gif" => Some(Format::Gif),
+            "txt" => Some(Format::Terminal),
+            _ => None,
+        }
+    }
+
+    // Whether the format is made of pixels, rather than vectors or text
+    pub fn is_raster(self) -> bool {
+        matches!(self, Format::Png | Format::Jpeg | Format::Bmp | Format::Gif)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Format, String> {
+        match name.to_lowercase().as_str() {
+            "png" => Ok(Format::Png),
+            "svg" => Ok(Format::Svg),
+            "jpg" | "jpeg" => Ok(Format::Jpeg),
+            "bmp" => Ok(Format::Bmp),
+            "gif" => Ok(Format::Gif),
+            "terminal" | "text" => Ok(Format::Terminal),
+            "compact" => Ok(Format::Compact),
+            _ => Err(format!("Unknown format {}", name)),
+        }
+    }
+}
+
+// Writes the code out in any of the formats
+pub fn write<W: Write>(code: &QR, mut output: W, format: Format, options: &RenderOptions) -> ImageResult<()> {
+    match format {
+        Format::Svg => Ok(output.write_all(svg::svg(code, options).as_bytes())?),
+        Format::Terminal => Ok(writeln!(output, "{}", terminal(code, options))?),
+        Format::Compact => Ok(writeln!(output, "{}", terminal_compact(code, options))?),
+        _ => write_raster(&render(code, options).map_err(image_error)?, output, format, options.dpi),
+    }
+}
+
+// Writes an image that's already been drawn in one of the raster formats
+// JPEG has no alpha channel, so it's dropped
+pub fn write_raster<W: Write>(image: &RgbaImage, mut output: W, format: Format, dpi: Option<u32>) -> ImageResult<()> {
+    let dynamic = || DynamicImage::ImageRgba8(image.clone());
+
+    match format {
+        Format::Jpeg => DynamicImage::ImageRgb8(dynamic().to_rgb()).write_to(&mut output, ImageOutputFormat::Jpeg(90)),
+        Format::Bmp => dynamic().write_to(&mut output, ImageOutputFormat::Bmp),
+        Format::Gif => dynamic().write_to(&mut output, ImageOutputFormat::Gif),
+        _ => write_png(output, image, dpi),
+    }
+}
+
+// Saves the code, using the extension to pick the format
+pub fn save(code: &QR, path: &str, options: &RenderOptions) -> ImageResult<()> {
+    match Format::from_path(path) {
+        Some(Format::Svg) => Ok(fs::write(path, svg::svg(code, options))?),
+        _ => save_raster(&render(code, options).map_err(image_error)?, path, options.dpi),
+    }
+}
+
+// Saves an image that's already been drawn, using the extension to pick the format
+pub fn save_raster(image: &RgbaImage, path: &str, dpi: Option<u32>) -> ImageResult<()> {
+    let is_png = Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+
+    if is_png {
+        save_png(image, path, dpi)
+    } else {
+        image.save(path)
+    }
+}
+
+// Saves a PNG, recording the DPI if there is one
+// Only PNGs get the DPI, other formats are saved without it
+pub fn save_png(image: &RgbaImage, path: &str, dpi: Option<u32>) -> ImageResult<()> {
+    write_png(BufWriter::new(File::create(path)?), image, dpi)
+}
+
+// Encodes a PNG into anything that can be written to
+pub fn write_png<W: Write>(output: W, image: &RgbaImage, dpi: Option<u32>) -> ImageResult<()> {
+    let mut encoder = png::Encoder::new(output, image.width(), image.height());
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+
+    // pHYs has to come before the image data
+    if let Some(dpi) = dpi {
+        // PNG stores pixels per metre, 1 inch = 0.0254 metres
+        let pixels_per_metre = ((dpi as f64) / 0.0254).round() as u32;
+
+        let mut phys = vec![];
+        phys.extend_from_slice(&pixels_per_metre.to_be_bytes());
+        phys.extend_from_slice(&pixels_per_metre.to_be_bytes());
+        // Unit specifier 1 means the values are in metres
+        phys.push(1);
+
+        writer.write_chunk(png::chunk::pHYs, &phys).map_err(io::Error::from)?;
+    }
+
+    writer.write_image_data(image).map_err(io::Error::from)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_cause_of_image_errors() {
+        let too_large = image_error(Error::ImageTooLarge { max: MAX_IMAGE_SIZE });
+        assert!(matches!(too_large, ImageError::Limits(_)));
+
+        let other = image_error(Error::TooLongForVersion(1));
+        assert!(matches!(other, ImageError::Parameter(_)));
+        assert!(other.to_string().ends_with(&Error::TooLongForVersion(1).to_string()));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::error::Error;
+use crate::qr::{EcLevel, QR};
+use crate::segment::Segment;
+use crate::render::{layout, RenderOptions};
+use image::imageops::{self, FilterType};
+use image::{GrayImage, RgbaImage};
+
+// Each module is split into a 3×3 grid of sub-cells, and only the middle one has to show the code
+pub const CELLS: usize = 3;
+
+// Anything in the picture darker than this counts as dark
+const THRESHOLD: u8 = 128;
+
+// Generates a code whose modules follow the picture as closely as possible while still decoding to the input
+// The padding after the message is never read, so its bits are set to whatever the picture needs,
+// and the mask is picked by how well the result matches the picture rather than by the usual penalties
+pub fn fit(segments: Vec<Segment>, ec_level: EcLevel, picture: &GrayImage) -> Result<QR, Error> {
+    let mut code = QR::with_segments(segments, ec_level)?;
+    code.generate();
+
+    // The picture at one pixel per module
+    let size = code.size as u32;
+    let target = imageops::resize(picture, size, size, FilterType::Triangle);
+
+    let mut best_mask = 0;
+    let mut best_mismatch = u64::MAX;
+
+    for mask in 0..8 {
+        set_padding(&mut code, &target, mask);
+        code.generate_with_mask(mask)?;
+
+        let mismatch = mismatch(&code, &target);
+
+        if mismatch < best_mismatch {
+            best_mask = mask;
+            best_mismatch = mismatch;
+        }
+    }
+
+    set_padding(&mut code, &target, best_mask);
+    code.generate_with_mask(best_mask)?;
+    Ok(code)
+}
+
+// Sets every padding bit so its module comes out the same color as the picture under the given mask
+fn set_padding(code: &mut QR, target: &GrayImage, mask: usize) {
+    let padding = code.padding_codewords();
+
+    for y in 0..code.size {
+        for x in 0..code.size {
+            if let Some((codeword, bit)) = code.data_bit_at(x, y) {
+                if padding.contains(&codeword) {
+                    let dark = target.get_pixel(x as u32, y as u32)[0] < THRESHOLD;
+
+                    // The mask flips the bit on its way to the module, so flip it first to cancel that out
+                    code.set_data_bit(codeword, bit, dark != QR::mask_applies(mask, x, y));
+                }
+            }
+        }
+    }
+}
+
+// How badly the modules clash with the picture, with clashes in the darkest and lightest parts counting the most
+fn mismatch(code: &QR, target: &GrayImage) -> u64 {
+    let mut mismatch = 0;
+
+    for y in 0..code.size {
+        for x in 0..code.size {
+            let level = target.get_pixel(x as u32, y as u32)[0];
+
+            if !code.is_function(x, y) && (level < THRESHOLD) != code.is_dark(x, y) {
+                mismatch += (level as i64 - THRESHOLD as i64).unsigned_abs();
+            }
+        }
+    }
+
+    mismatch
+}
+
+// Splits every module into sub-cells, true where the sub-cell is dark
+// Function patterns and the middle of each module show the code, the rest of the sub-cells show the picture
+pub fn cells(code: &QR, picture: &GrayImage) -> Vec<Vec<bool>> {
+    let size = code.size * CELLS;
+    let target = imageops::resize(picture, size as u32, size as u32, FilterType::Triangle);
+
+    (0..size)
+        .map(|y| {
+            (0..size)
+                .map(|x| {
+                    let module_x = x / CELLS;
+                    let module_y = y / CELLS;
+                    let middle = x % CELLS == CELLS / 2 && y % CELLS == CELLS / 2;
+
+                    if middle || code.is_function(module_x, module_y) {
+                        code.is_dark(module_x, module_y)
+                    } else {
+                        target.get_pixel(x as u32, y as u32)[0] < THRESHOLD
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Draws the code with each module split into sub-cells, so the picture shows through
+pub fn render(code: &QR, picture: &GrayImage, options: &RenderOptions) -> Result<RgbaImage, Error> {
+    let (scale, image_size, offset) = layout(code, options, CELLS as u32)?;
+    let cell_size = scale / CELLS as u32;
+
+    let mut image = RgbaImage::from_pixel(image_size, image_size, options.light);
+
+    for (y, row) in cells(code, picture).iter().enumerate() {
+        for (x, &dark) in row.iter().enumerate() {
+            if !dark {
+                continue;
+            }
+
+            let left = offset + x as u32 * cell_size;
+            let top = offset + y as u32 * cell_size;
+
+            for pixel_y in top..top + cell_size {
+                for pixel_x in left..left + cell_size {
+                    image.put_pixel(pixel_x, pixel_y, options.dark);
+                }
+            }
+        }
+    }
+
+    Ok(image)
+}
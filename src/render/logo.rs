@@ -0,0 +1,114 @@
+use crate::error::Error;
+use crate::qr::{EcLevel, QR};
+use crate::segment::Segment;
+use image::RgbaImage;
+use std::collections::{HashMap, HashSet};
+
+// A picture placed in the middle of the code, relying on error correction to make up for the modules it hides
+#[derive(Clone)]
+pub struct Logo {
+    // Picture drawn over the cleared area, or None to just leave the area blank
+    pub image: Option<RgbaImage>,
+    // Width of the cleared area as a fraction of the width of the code
+    pub size: f64,
+}
+
+// A rectangle of modules
+#[derive(Clone, Copy, Debug)]
+pub struct Area {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Area {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
+// Rounds up to the next odd number, so an area of that many modules can sit exactly in the middle of the code
+fn odd(modules: f64) -> usize {
+    let modules = modules.round().max(1.0) as usize;
+    modules | 1
+}
+
+impl Logo {
+    // The modules hidden by the logo, keeping to the picture's aspect ratio
+    pub fn area(&self, code: &QR) -> Area {
+        let width = odd(code.size as f64 * self.size).min(code.size);
+
+        let height = match &self.image {
+            Some(image) => odd(width as f64 * image.height() as f64 / image.width() as f64).min(code.size),
+            None => width,
+        };
+
+        Area {
+            x: (code.size - width) / 2,
+            y: (code.size - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    // Function pattern modules under the logo, which have to be drawn back on top of it
+    pub fn function_modules(&self, code: &QR) -> Vec<(usize, usize)> {
+        let area = self.area(code);
+        let mut modules = vec![];
+
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                if code.is_function(x, y) {
+                    modules.push((x, y));
+                }
+            }
+        }
+
+        modules
+    }
+
+    // Makes sure every block can still be corrected with the logo in place
+    // Function patterns are always drawn over the logo, so only data modules count
+    pub fn check(&self, code: &QR) -> Result<(), Error> {
+        let area = self.area(code);
+        let mut damaged: HashMap<usize, HashSet<usize>> = HashMap::new();
+
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                if code.is_function(x, y) {
+                    continue;
+                }
+
+                if let Some((codeword, block)) = code.codeword_at(x, y) {
+                    damaged.entry(block).or_default().insert(codeword);
+                }
+            }
+        }
+
+        let worst = damaged.values().map(|codewords| codewords.len()).max().unwrap_or(0);
+        let correctable = code.correctable_codewords();
+
+        if worst > correctable {
+            return Err(Error::LogoTooBig { damaged: worst, correctable });
+        }
+
+        Ok(())
+    }
+}
+
+// Generates a code that can still be read with the logo on top
+// If bump is set, higher error correction levels are tried before giving up
+pub fn fit(segments: Vec<Segment>, ec_level: EcLevel, logo: &Logo, bump: bool) -> Result<QR, Error> {
+    let mut ec_level = ec_level;
+
+    loop {
+        let mut code = QR::with_segments(segments.clone(), ec_level)?;
+        code.generate();
+
+        match (logo.check(&code), ec_level.next()) {
+            (Err(_), Some(next)) if bump => ec_level = next,
+            (result, _) => return result.map(|_| code),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::decode;
+use crate::error::Error;
+use crate::qr::{EcLevel, QR};
+use crate::render::{self, layout, RenderOptions};
+use crate::segment::Segment;
+use image::{GrayImage, ImageResult, Luma, Rgba, RgbaImage};
+
+// Three codes layered into the red, green and blue channels of one image, for three times the data on screens
+// Each channel is dark wherever its own code is dark, so the colors and quiet zone come from the codes themselves
+
+// Splits an input into three parts of roughly equal size, one for each channel
+// The splits only land between characters, so each part is still valid text
+pub fn split(input: &str) -> [String; 3] {
+    let mut cuts = [0; 2];
+
+    for (i, cut) in cuts.iter_mut().enumerate() {
+        let mut at = input.len() * (i + 1) / 3;
+
+        while !input.is_char_boundary(at) {
+            at += 1;
+        }
+
+        *cut = at;
+    }
+
+    [
+        input[..cuts[0]].to_string(),
+        input[cuts[0]..cuts[1]].to_string(),
+        input[cuts[1]..].to_string(),
+    ]
+}
+
+// Generates a code for each channel, all at the same version so their modules line up
+pub fn encode(inputs: [String; 3], ec_level: EcLevel) -> Result<[QR; 3], Error> {
+    // Any part too long for every version means the whole thing is, however the rest of it was split
+    let versions: Option<Vec<usize>> = inputs
+        .iter()
+        .map(|input| QR::smallest_version(&[Segment::bytes(input.as_bytes())], ec_level))
+        .collect();
+
+    let version = versions.and_then(|versions| versions.into_iter().max()).ok_or(Error::TooLong {
+        max: QR::max_length(ec_level),
+    })?;
+
+    let [red, green, blue] = inputs;
+    let mut codes = [
+        QR::with_version(red, ec_level, version)?,
+        QR::with_version(green, ec_level, version)?,
+        QR::with_version(blue, ec_level, version)?,
+    ];
+
+    for code in codes.iter_mut() {
+        code.generate();
+    }
+
+    Ok(codes)
+}
+
+// Draws the codes into the red, green and blue channels in that order
+pub fn render(codes: &[QR; 3], options: &RenderOptions) -> Result<RgbaImage, Error> {
+    let size = codes[0].size;
+    let (scale, image_size, offset) = layout(&codes[0], options, 1)?;
+
+    Ok(RgbaImage::from_fn(image_size, image_size, |x, y| {
+        let mut pixel = Rgba([255u8; 4]);
+
+        if x >= offset && y >= offset {
+            let module_x = ((x - offset) / scale) as usize;
+            let module_y = ((y - offset) / scale) as usize;
+
+            if module_x < size && module_y < size {
+                for (channel, code) in codes.iter().enumerate() {
+                    if code.is_dark(module_x, module_y) {
+                        pixel[channel] = 0;
+                    }
+                }
+            }
+        }
+
+        pixel
+    }))
+}
+
+// Saves the codes as an image, picking the format from the extension
+// Lossy formats like JPEG bleed the channels into each other, so stick to PNG where possible
+pub fn save(codes: &[QR; 3], path: &str, options: &RenderOptions) -> ImageResult<()> {
+    render::save_raster(&render(codes, options).map_err(render::image_error)?, path, options.dpi)
+}
+
+// Splits the image into its red, green and blue channels and reads the code in each
+pub fn decode(image: &RgbaImage) -> Result<[Vec<u8>; 3], Error> {
+    let channel = |channel: usize| {
+        let gray = GrayImage::from_fn(image.width(), image.height(), |x, y| Luma([image.get_pixel(x, y)[channel]]));
+        decode::decode_image(&gray)
+    };
+
+    Ok([channel(0)?, channel(1)?, channel(2)?])
+}
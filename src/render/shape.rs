@@ -0,0 +1,225 @@
+use crate::qr::QR;
+use crate::render::RenderOptions;
+use std::str::FromStr;
+
+// How far the corners of rounded modules are cut in, as a fraction of the module
+const ROUNDED_RADIUS: f64 = 0.3;
+
+// Shape used for each data module
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModuleShape {
+    Square,
+    Circle,
+    Rounded,
+    // Rounded dots that join up with their neighbours into rounded runs
+    Connected,
+}
+
+// Shape used for the outer ring and the inner eye of the finder patterns
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FinderShape {
+    Square,
+    Circle,
+    Rounded,
+}
+
+impl FromStr for ModuleShape {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ModuleShape, String> {
+        match name {
+            "square" => Ok(ModuleShape::Square),
+            "circle" => Ok(ModuleShape::Circle),
+            "rounded" => Ok(ModuleShape::Rounded),
+            "connected" => Ok(ModuleShape::Connected),
+            _ => Err(format!("Unknown module shape {}", name)),
+        }
+    }
+}
+
+impl FromStr for FinderShape {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<FinderShape, String> {
+        match name {
+            "square" => Ok(FinderShape::Square),
+            "circle" => Ok(FinderShape::Circle),
+            "rounded" => Ok(FinderShape::Rounded),
+            _ => Err(format!("Unknown finder shape {}", name)),
+        }
+    }
+}
+
+// A square in module coordinates with its corners rounded off, which every drawn shape is made from
+// Radii go clockwise from the top-left corner, so a circle is just a tile with every radius at half the size
+#[derive(Clone, Copy, Debug)]
+pub struct Tile {
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+    pub radii: [f64; 4],
+}
+
+impl Tile {
+    fn new(x: f64, y: f64, size: f64, radius: f64) -> Tile {
+        Tile { x, y, size, radii: [radius; 4] }
+    }
+
+    fn finder(x: f64, y: f64, size: f64, shape: FinderShape) -> Tile {
+        let radius = match shape {
+            FinderShape::Square => 0.0,
+            FinderShape::Circle => size / 2.0,
+            FinderShape::Rounded => size / 7.0,
+        };
+
+        Tile::new(x, y, size, radius)
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let right = self.x + self.size;
+        let bottom = self.y + self.size;
+
+        if x < self.x || y < self.y || x >= right || y >= bottom {
+            return false;
+        }
+
+        // Centre of each corner's arc, in the same clockwise order as the radii
+        let [top_left, top_right, bottom_right, bottom_left] = self.radii;
+        let corners = [
+            (self.x + top_left, self.y + top_left, top_left),
+            (right - top_right, self.y + top_right, top_right),
+            (right - bottom_right, bottom - bottom_right, bottom_right),
+            (self.x + bottom_left, bottom - bottom_left, bottom_left),
+        ];
+
+        for (i, &(centre_x, centre_y, radius)) in corners.iter().enumerate() {
+            // Only points out past the centre of the arc, towards the corner, can be cut off
+            let past_x = if i == 0 || i == 3 { x < centre_x } else { x > centre_x };
+            let past_y = if i < 2 { y < centre_y } else { y > centre_y };
+
+            if radius > 0.0 && past_x && past_y {
+                let distance = (x - centre_x).powi(2) + (y - centre_y).powi(2);
+                if distance > radius * radius {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    // SVG path data tracing the tile clockwise, moved right and down by offset
+    pub fn path(&self, offset: f64) -> String {
+        let left = self.x + offset;
+        let top = self.y + offset;
+        let right = left + self.size;
+        let bottom = top + self.size;
+        let [top_left, top_right, bottom_right, bottom_left] = self.radii;
+
+        let mut path = format!("M{} {}H{}", left + top_left, top, right - top_right);
+        path += &arc(top_right, right, top + top_right);
+        path += &format!("V{}", bottom - bottom_right);
+        path += &arc(bottom_right, right - bottom_right, bottom);
+        path += &format!("H{}", left + bottom_left);
+        path += &arc(bottom_left, left, bottom - bottom_left);
+        path += &format!("V{}", top + top_left);
+        path += &arc(top_left, left + top_left, top);
+        path + "Z"
+    }
+}
+
+// Clockwise quarter circle to the given point, or nothing for a square corner
+fn arc(radius: f64, x: f64, y: f64) -> String {
+    if radius > 0.0 {
+        format!("A{} {} 0 0 1 {} {}", radius, radius, x, y)
+    } else {
+        String::new()
+    }
+}
+
+// A dark area of the code, optionally with a light hole cut out of it
+pub struct Piece {
+    pub outer: Tile,
+    pub hole: Option<Tile>,
+}
+
+impl Piece {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.outer.contains(x, y) && !self.hole.is_some_and(|hole| hole.contains(x, y))
+    }
+}
+
+// Breaks the code down into the dark pieces that need drawing, in module coordinates
+pub fn pieces(code: &QR, options: &RenderOptions) -> Vec<Piece> {
+    let finders = code.finder_positions();
+    let in_finder = |x: usize, y: usize| {
+        finders
+            .iter()
+            .any(|&(finder_x, finder_y)| x >= finder_x && x < finder_x + 7 && y >= finder_y && y < finder_y + 7)
+    };
+
+    // Data modules under the logo are left out entirely
+    let logo_area = options.logo.as_ref().map(|logo| logo.area(code));
+    let is_hidden = |x: usize, y: usize| !code.is_function(x, y) && logo_area.is_some_and(|area| area.contains(x, y));
+
+    // Anything off the edge of the code counts as light
+    let is_dark = |x: isize, y: isize| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < code.size
+            && (y as usize) < code.size
+            && code.is_dark(x as usize, y as usize)
+            && !is_hidden(x as usize, y as usize)
+    };
+
+    let mut pieces = vec![];
+
+    for &(x, y) in finders.iter() {
+        let (x, y) = (x as f64, y as f64);
+
+        pieces.push(Piece {
+            outer: Tile::finder(x, y, 7.0, options.finder_ring),
+            hole: Some(Tile::finder(x + 1.0, y + 1.0, 5.0, options.finder_ring)),
+        });
+
+        pieces.push(Piece {
+            outer: Tile::finder(x + 2.0, y + 2.0, 3.0, options.finder_eye),
+            hole: None,
+        });
+    }
+
+    for y in 0..code.size {
+        for x in 0..code.size {
+            if in_finder(x, y) || !is_dark(x as isize, y as isize) {
+                continue;
+            }
+
+            // Timing and alignment patterns stay square so scanners can still lock on to them
+            let shape = if code.is_function(x, y) { ModuleShape::Square } else { options.module_shape };
+
+            let radii = match shape {
+                ModuleShape::Square => [0.0; 4],
+                ModuleShape::Circle => [0.5; 4],
+                ModuleShape::Rounded => [ROUNDED_RADIUS; 4],
+                ModuleShape::Connected => {
+                    // Only round a corner if neither of the modules next to it are dark
+                    let (x, y) = (x as isize, y as isize);
+                    let left = is_dark(x - 1, y);
+                    let right = is_dark(x + 1, y);
+                    let above = is_dark(x, y - 1);
+                    let below = is_dark(x, y + 1);
+
+                    let round = |joined: bool| if joined { 0.0 } else { 0.5 };
+                    [round(left || above), round(right || above), round(right || below), round(left || below)]
+                }
+            };
+
+            pieces.push(Piece {
+                outer: Tile { x: x as f64, y: y as f64, size: 1.0, radii },
+                hole: None,
+            });
+        }
+    }
+
+    pieces
+}
@@ -0,0 +1,126 @@
+use crate::qr::QR;
+use crate::render::{halftone, swiss};
+use crate::render::shape::{self, FinderShape, ModuleShape};
+use crate::render::{self, RenderOptions, Scale};
+use image::Rgba;
+
+// SVG fill attributes for a color, with the alpha split out since SVG hex colors can't carry it
+fn fill(color: Rgba<u8>) -> String {
+    let Rgba([red, green, blue, alpha]) = color;
+    let mut fill = format!("fill=\"#{:02x}{:02x}{:02x}\"", red, green, blue);
+
+    if alpha < 255 {
+        fill += &format!(" fill-opacity=\"{}\"", alpha as f64 / 255.0);
+    }
+
+    fill
+}
+
+// Draws the code as an SVG document, using one module as the unit of the view box
+pub fn svg(code: &QR, options: &RenderOptions) -> String {
+    let quiet_zone = options.quiet_zone as f64;
+    let modules = code.size as f64 + quiet_zone * 2.0;
+
+    // Vectors scale cleanly, so there's no need to round to whole pixels per module
+    let size = match options.scale {
+        Scale::PerModule(scale) => modules * scale as f64,
+        Scale::Fit(size) => size as f64,
+    };
+
+    // Square modules are drawn with crisp edges so neighbours don't leave hairline gaps between them,
+    // but curves need antialiasing to look right
+    let all_square = options.module_shape == ModuleShape::Square
+        && options.finder_ring == FinderShape::Square
+        && options.finder_eye == FinderShape::Square;
+    let rendering = if all_square { "crispEdges" } else { "geometricPrecision" };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"{}\">\n",
+        size, size, modules, modules, rendering
+    );
+
+    // A fully transparent background doesn't need drawing at all
+    if options.light[3] > 0 {
+        svg += &format!("<rect width=\"{}\" height=\"{}\" {}/>\n", modules, modules, fill(options.light));
+    }
+
+    if let Some(logo) = &options.logo {
+        let area = logo.area(code);
+
+        if let Some(picture) = &logo.image {
+            // The picture is embedded as a PNG so the SVG stays a single file
+            let mut png = vec![];
+            render::write_png(&mut png, picture, None).expect("Writing to memory can't fail");
+
+            svg += &format!(
+                "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"xMidYMid meet\" href=\"data:image/png;base64,{}\"/>\n",
+                area.x as f64 + quiet_zone,
+                area.y as f64 + quiet_zone,
+                area.width,
+                area.height,
+                base64::encode(&png)
+            );
+        }
+
+        // Light function modules get painted back over the picture, the dark ones are drawn with everything else
+        let path: String = logo
+            .function_modules(code)
+            .iter()
+            .map(|&(x, y)| format!("M{} {}h1v1h-1Z", x as f64 + quiet_zone, y as f64 + quiet_zone))
+            .collect();
+
+        if !path.is_empty() {
+            svg += &format!("<path {} d=\"{}\"/>\n", fill(options.light), path);
+        }
+    }
+
+    if let Some(picture) = &options.halftone {
+        // Sub-cells are drawn a whole unit wide, then the path is scaled down to fit them into their modules
+        let cells = halftone::CELLS as f64;
+        let mut path = String::new();
+
+        for (y, row) in halftone::cells(code, picture).iter().enumerate() {
+            for (x, &dark) in row.iter().enumerate() {
+                if dark {
+                    path += &format!("M{} {}h1v1h-1Z", x as f64 + quiet_zone * cells, y as f64 + quiet_zone * cells);
+                }
+            }
+        }
+
+        svg += &format!("<path transform=\"scale({})\" {} d=\"{}\"/>\n", 1.0 / cells, fill(options.dark), path);
+        return svg + "</svg>\n";
+    }
+
+    // Every piece goes into one path, even-odd filling cuts the holes out of the finder rings
+    let path: String = shape::pieces(code, options)
+        .iter()
+        .map(|piece| {
+            let mut path = piece.outer.path(quiet_zone);
+
+            if let Some(hole) = piece.hole {
+                path += &hole.path(quiet_zone);
+            }
+
+            path
+        })
+        .collect();
+
+    svg += &format!("<path fill-rule=\"evenodd\" {} d=\"{}\"/>\n", fill(options.dark), path);
+
+    if options.swiss_cross {
+        for rect in swiss::cross(code) {
+            let color = if rect.dark { options.dark } else { options.light };
+
+            svg += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>\n",
+                rect.x + quiet_zone,
+                rect.y + quiet_zone,
+                rect.width,
+                rect.height,
+                fill(color)
+            );
+        }
+    }
+
+    svg + "</svg>\n"
+}
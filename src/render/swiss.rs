@@ -0,0 +1,47 @@
+use crate::qr::QR;
+
+// The Swiss cross that has to sit in the middle of every QR-bill code
+// It's 7 mm across on a code that's 46 mm across without its quiet zone, so it's sized off the code rather
+// than the modules, and comes out at exactly 7 mm whenever the code is printed at the size the spec asks for
+pub const CROSS_SIZE: f64 = 7.0 / 46.0;
+
+// A rectangle of the cross in module coordinates, drawn in order so each one covers the ones before it
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub dark: bool,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
+// A light border, a dark square, then the two light bars of the cross itself
+// The square is 6 mm of the 7, and the cross has the proportions of the one on the flag
+pub fn cross(code: &QR) -> [Rect; 4] {
+    let centre = code.size as f64 / 2.0;
+    let size = code.size as f64 * CROSS_SIZE;
+    let square = size * 6.0 / 7.0;
+    let arm_width = square * 6.0 / 32.0;
+    let arm_length = square * 20.0 / 32.0;
+
+    let centred = |width: f64, height: f64, dark: bool| Rect {
+        x: centre - width / 2.0,
+        y: centre - height / 2.0,
+        width,
+        height,
+        dark,
+    };
+
+    [
+        centred(size, size, false),
+        centred(square, square, true),
+        centred(arm_length, arm_width, false),
+        centred(arm_width, arm_length, false),
+    ]
+}
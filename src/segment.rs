@@ -0,0 +1,200 @@
+use crate::error::Error;
+use serde::Serialize;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+// Characters alphanumeric mode can hold, in the order of their values
+pub const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+// How the characters of a segment are packed into bits
+// Numeric fits 3 digits in 10 bits and alphanumeric 2 characters in 11, so both beat byte mode's 8 bits a character
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Numeric,
+    Alphanumeric,
+    Byte,
+}
+
+impl Mode {
+    // The 4 bit mode indicator that starts each segment
+    pub fn indicator(self) -> usize {
+        match self {
+            Mode::Numeric => 0b0001,
+            Mode::Alphanumeric => 0b0010,
+            Mode::Byte => 0b0100,
+        }
+    }
+
+    pub fn from_indicator(indicator: usize) -> Option<Mode> {
+        match indicator {
+            0b0001 => Some(Mode::Numeric),
+            0b0010 => Some(Mode::Alphanumeric),
+            0b0100 => Some(Mode::Byte),
+            _ => None,
+        }
+    }
+
+    // Length of the character count in bits, which grows with the version
+    pub fn char_count_bits(self, version: usize) -> usize {
+        let group = match version {
+            1..=9 => 0,
+            10..=26 => 1,
+            _ => 2,
+        };
+
+        match self {
+            Mode::Numeric => [10, 12, 14][group],
+            Mode::Alphanumeric => [9, 11, 13][group],
+            Mode::Byte => [8, 16, 16][group],
+        }
+    }
+
+    // Whether every byte of the data can be written in this mode
+    pub fn can_encode(self, data: &[u8]) -> bool {
+        match self {
+            Mode::Numeric => data.iter().all(u8::is_ascii_digit),
+            Mode::Alphanumeric => data.iter().all(|&byte| ALPHANUMERIC.contains(byte as char)),
+            Mode::Byte => true,
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Mode, String> {
+        match name.to_lowercase().as_str() {
+            "numeric" => Ok(Mode::Numeric),
+            "alphanumeric" => Ok(Mode::Alphanumeric),
+            "byte" => Ok(Mode::Byte),
+            _ => Err(format!("Unknown mode {}", name)),
+        }
+    }
+}
+
+// A run of data written in a single mode, codes can hold any number of them one after another
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub mode: Mode,
+    // The characters themselves, as ASCII for numeric and alphanumeric segments
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn bytes(data: &[u8]) -> Segment {
+        Segment {
+            mode: Mode::Byte,
+            data: data.to_vec(),
+        }
+    }
+
+    // A segment in a specific mode, which has to be able to hold every character
+    pub fn with_mode(mode: Mode, data: &[u8]) -> Result<Segment, Error> {
+        if !mode.can_encode(data) {
+            return Err(Error::InvalidField {
+                field: "segment",
+                reason: format!("data has characters that can't be written in {:?} mode", mode),
+            });
+        }
+
+        Ok(Segment { mode, data: data.to_vec() })
+    }
+
+    // A segment in the densest mode that can hold all of the data
+    pub fn auto(data: &[u8]) -> Segment {
+        let mode = [Mode::Numeric, Mode::Alphanumeric]
+            .iter()
+            .copied()
+            .find(|mode| mode.can_encode(data))
+            .unwrap_or(Mode::Byte);
+
+        Segment { mode, data: data.to_vec() }
+    }
+
+    // Bits taken up by the characters alone
+    fn data_bits(&self) -> usize {
+        let length = self.data.len();
+
+        match self.mode {
+            Mode::Numeric => length / 3 * 10 + [0, 4, 7][length % 3],
+            Mode::Alphanumeric => length / 2 * 11 + length % 2 * 6,
+            Mode::Byte => length * 8,
+        }
+    }
+
+    // Bits the whole segment takes up at a version, or None if there are too many characters for its count
+    pub fn bits(&self, version: usize) -> Option<usize> {
+        let count_bits = self.mode.char_count_bits(version);
+
+        if self.data.len() >= 1 << count_bits {
+            return None;
+        }
+
+        Some(4 + count_bits + self.data_bits())
+    }
+
+    // Total bits a list of segments takes up at a version
+    pub fn total_bits(segments: &[Segment], version: usize) -> Option<usize> {
+        segments
+            .iter()
+            .try_fold(0, |total, segment| segment.bits(version).map(|bits| total + bits))
+    }
+
+    pub(crate) fn write(&self, version: usize, writer: &mut BitWriter) {
+        writer.push(self.mode.indicator(), 4);
+        writer.push(self.data.len(), self.mode.char_count_bits(version));
+
+        match self.mode {
+            Mode::Numeric => {
+                // Groups of 3 digits as 10 bit numbers, with 2 or 1 left over taking 7 or 4 bits
+                for group in self.data.chunks(3) {
+                    let value = group.iter().fold(0, |value, digit| value * 10 + (digit - b'0') as usize);
+                    writer.push(value, [0, 4, 7, 10][group.len()]);
+                }
+            }
+            Mode::Alphanumeric => {
+                // Pairs of characters as 45 * first + second in 11 bits, with one left over taking 6
+                let value = |byte: u8| ALPHANUMERIC.find(byte as char).unwrap_or(0);
+
+                for pair in self.data.chunks(2) {
+                    match *pair {
+                        [first, second] => writer.push(value(first) * 45 + value(second), 11),
+                        [last] => writer.push(value(last), 6),
+                        _ => (),
+                    }
+                }
+            }
+            Mode::Byte => {
+                for &byte in self.data.iter() {
+                    writer.push(byte as usize, 8);
+                }
+            }
+        }
+    }
+}
+
+// Packs values into bytes, most significant bit first
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    pub bytes: Vec<u8>,
+    pub length: usize,
+}
+
+impl BitWriter {
+    pub fn push(&mut self, value: usize, bits: usize) {
+        for i in (0..bits).rev() {
+            if self.length.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.length % 8);
+            }
+
+            self.length += 1;
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use crate::qr::{EcLevel, QR};
+use crate::render::logo::Logo;
+use crate::render::{self, Format, RenderOptions, Scale};
+use crate::segment::Segment;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Method};
+
+// A small HTTP server making codes on demand, like GET /qr?data=hello&format=svg&ec=H
+// A code only depends on the query, so every response gets an ETag made from it and can be cached for as long
+// as clients like, and requests that already have the ETag are answered without making the code again
+
+pub struct Config {
+    // Most bytes of data accepted, which by default is as much as any code can hold
+    pub max_data: usize,
+    // Widest image that can be asked for, in pixels
+    pub max_size: u32,
+    // Seconds clients and proxies can keep a code for
+    pub max_age: u32,
+    // Requests handled at once
+    pub threads: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_data: QR::max_length(EcLevel::L),
+            max_size: 4096,
+            max_age: 86_400,
+            threads: 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+    // Only codes get one, errors aren't cached
+    pub etag: Option<String>,
+}
+
+impl Response {
+    fn error(status: u16, message: impl Display) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", message).into_bytes(),
+            etag: None,
+        }
+    }
+}
+
+// Percent-decodes part of a query string, where + is also a space
+// The result is bytes rather than text, so binary data can be sent too
+fn percent_decode(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+
+        i += 1;
+    }
+
+    decoded
+}
+
+// Query parameters, where later ones win over earlier ones with the same name
+struct Query(HashMap<String, Vec<u8>>);
+
+// Everything that can be set in the query, anything else is a mistake
+const PARAMETERS: [&str; 18] = [
+    "data", "format", "ec", "mode", "version", "mask", "bump_ec", "matrix", "scale", "quiet_zone", "dpi", "dark",
+    "light", "shape", "finder_ring", "finder_eye", "logo_size", "swiss_cross",
+];
+
+impl Query {
+    fn parse(query: &str) -> Result<Query, Response> {
+        let mut parameters = HashMap::new();
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = String::from_utf8_lossy(&percent_decode(name)).into_owned();
+
+            if !PARAMETERS.contains(&name.as_str()) {
+                return Err(Response::error(400, format!("unknown parameter {}", name)));
+            }
+
+            parameters.insert(name, percent_decode(value));
+        }
+
+        Ok(Query(parameters))
+    }
+
+    fn text(&self, name: &str) -> Result<Option<&str>, Response> {
+        match self.0.get(name) {
+            Some(value) => std::str::from_utf8(value)
+                .map(Some)
+                .map_err(|_| Response::error(400, format!("{} isn't text", name))),
+            None => Ok(None),
+        }
+    }
+
+    fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, Response>
+    where
+        T::Err: Display,
+    {
+        match self.text(name)? {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|error| Response::error(400, format!("invalid {}: {}", name, error))),
+            None => Ok(None),
+        }
+    }
+
+    // Switches are on when they're given without a value, like &swiss_cross
+    fn switch(&self, name: &str) -> Result<bool, Response> {
+        match self.text(name)? {
+            None | Some("false") | Some("0") => Ok(false),
+            Some("") | Some("true") | Some("1") => Ok(true),
+            Some(value) => Err(Response::error(400, format!("{} has to be true or false, not {}", name, value))),
+        }
+    }
+}
+
+// ETags come from the query and the version of the crate, since nothing else changes what comes back
+fn etag(query: &str) -> String {
+    let hash = Sha256::digest(format!("{}?{}", env!("CARGO_PKG_VERSION"), query).as_bytes());
+    let hex: String = hash[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"{}\"", hex)
+}
+
+// Answers a request for a url like /qr?data=..., given the ETag the client already has if it sent one
+pub fn handle(url: &str, if_none_match: Option<&str>, config: &Config) -> Response {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    if path != "/qr" {
+        return Response::error(404, "codes are made at /qr?data=...");
+    }
+
+    // Percent-encoding makes data up to three times longer, and the other parameters need some room too
+    if query.len() > config.max_data * 3 + 1024 {
+        return Response::error(414, "query is too long");
+    }
+
+    let etag = etag(query);
+    let tags: Vec<&str> = if_none_match.map_or(vec![], |tags| tags.split(',').map(str::trim).collect());
+
+    let not_modified = |etag: String| Response {
+        status: 304,
+        content_type: "text/plain; charset=utf-8",
+        body: vec![],
+        etag: Some(etag),
+    };
+
+    // Only a query that made a code before has this ETag, so it can be answered without making the code again
+    if tags.contains(&etag.as_str()) {
+        return not_modified(etag);
+    }
+
+    // * matches anything, but bad queries still get their error rather than a 304
+    match generate(query, config) {
+        Ok(_) if tags.contains(&"*") => not_modified(etag),
+        Ok((content_type, body)) => Response {
+            status: 200,
+            content_type,
+            body,
+            etag: Some(etag),
+        },
+        Err(response) => response,
+    }
+}
+
+fn generate(query: &str, config: &Config) -> Result<(&'static str, Vec<u8>), Response> {
+    let query = Query::parse(query)?;
+    let data = query.0.get("data").ok_or_else(|| Response::error(400, "missing data"))?;
+
+    if data.len() > config.max_data {
+        return Err(Response::error(
+            413,
+            format!("data is {} bytes, but at most {} are allowed", data.len(), config.max_data),
+        ));
+    }
+
+    let segment = match query.text("mode")? {
+        None | Some("auto") => Segment::auto(data),
+        Some(mode) => {
+            let mode = mode.parse().map_err(|error| Response::error(400, error))?;
+            Segment::with_mode(mode, data).map_err(|error| Response::error(400, error))?
+        }
+    };
+
+    let mut options = RenderOptions::default();
+    options.scale = query.get("scale")?.unwrap_or(options.scale);
+    options.quiet_zone = query.get("quiet_zone")?.unwrap_or(options.quiet_zone);
+    options.dpi = query.get("dpi")?;
+    options.module_shape = query.get("shape")?.unwrap_or(options.module_shape);
+    options.finder_ring = query.get("finder_ring")?.unwrap_or(options.finder_ring);
+    options.finder_eye = query.get("finder_eye")?.unwrap_or(options.finder_eye);
+    options.swiss_cross = query.switch("swiss_cross")?;
+
+    for (name, color) in [("dark", &mut options.dark), ("light", &mut options.light)] {
+        if let Some(text) = query.text(name)? {
+            *color = render::parse_color(text)
+                .ok_or_else(|| Response::error(400, format!("{} isn't a color like 1a2b3c", text)))?;
+        }
+    }
+
+    // Only blank space can be left for logos, since there's nowhere to send the picture
+    if let Some(size) = query.get("logo_size")? {
+        options.logo = Some(Logo { image: None, size });
+    }
+
+    let code = render::fit(
+        vec![segment],
+        query.get("ec")?.unwrap_or(EcLevel::Q),
+        query.get("version")?,
+        query.get("mask")?,
+        query.switch("bump_ec")?,
+        &options,
+    )
+    .map_err(|error| Response::error(400, error))?;
+
+    let format = match query.text("format")? {
+        Some("json") => {
+            let metadata = code.metadata(query.switch("matrix")?);
+            let json = serde_json::to_vec(&metadata).map_err(|error| Response::error(500, error))?;
+            return Ok(("application/json", json));
+        }
+        Some(format) => format.parse().map_err(|error| Response::error(400, error))?,
+        None => Format::Png,
+    };
+
+    // Checked in 64 bits since the scale and quiet zone can be anything that fits in 32
+    let modules = code.size as u64 + options.quiet_zone as u64 * 2;
+
+    let width = match options.scale {
+        Scale::PerModule(scale) => modules * scale as u64,
+        Scale::Fit(size) => modules.max(size as u64),
+    };
+
+    if width > config.max_size as u64 {
+        return Err(Response::error(
+            400,
+            format!("image would be {} pixels wide, but at most {} are allowed", width, config.max_size),
+        ));
+    }
+
+    let content_type = match format {
+        Format::Png => "image/png",
+        Format::Svg => "image/svg+xml",
+        Format::Jpeg => "image/jpeg",
+        Format::Bmp => "image/bmp",
+        Format::Gif => "image/gif",
+        Format::Terminal | Format::Compact => "text/plain; charset=utf-8",
+    };
+
+    let mut body = vec![];
+    render::write(&code, &mut body, format, &options).map_err(|error| Response::error(500, error))?;
+
+    Ok((content_type, body))
+}
+
+pub struct Server {
+    server: Arc<tiny_http::Server>,
+    config: Arc<Config>,
+}
+
+impl Server {
+    // Starts listening on an address like 127.0.0.1:8080, where port 0 picks any free port
+    pub fn bind(address: &str, config: Config) -> Result<Server, Box<dyn Error + Send + Sync>> {
+        Ok(Server {
+            server: Arc::new(tiny_http::Server::http(address)?),
+            config: Arc::new(config),
+        })
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    // Answers requests on as many threads as the config has, until the listener stops working
+    pub fn run(self) {
+        let workers: Vec<_> = (0..self.config.threads.max(1))
+            .map(|_| {
+                let server = self.server.clone();
+                let config = self.config.clone();
+
+                thread::spawn(move || {
+                    while let Ok(request) = server.recv() {
+                        let response = match request.method() {
+                            // HEAD requests get the same headers, tiny_http leaves the body out for them
+                            Method::Get | Method::Head => {
+                                let if_none_match = request
+                                    .headers()
+                                    .iter()
+                                    .find(|header| header.field.equiv("If-None-Match"))
+                                    .map(|header| header.value.to_string());
+
+                                handle(request.url(), if_none_match.as_deref(), &config)
+                            }
+                            _ => Response::error(405, "only GET and HEAD are allowed"),
+                        };
+
+                        // Clients hanging up early isn't the server's problem
+                        let _ = request.respond(http_response(response, &config));
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn http_response(response: Response, config: &Config) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+    let header = |name: &str, value: &str| Header::from_bytes(name, value).unwrap();
+
+    let cache_control = match response.etag {
+        Some(_) => format!("public, max-age={}", config.max_age),
+        None => "no-store".to_string(),
+    };
+
+    let mut http = tiny_http::Response::from_data(response.body)
+        .with_status_code(response.status)
+        .with_header(header("Content-Type", response.content_type))
+        .with_header(header("Cache-Control", &cache_control));
+
+    if let Some(etag) = &response.etag {
+        http = http.with_header(header("ETag", etag));
+    }
+
+    http
+}
@@ -0,0 +1,164 @@
+use crate::qr::{EcLevel, QR};
+use crate::render::{self, svg, RenderOptions};
+use crate::segment::Segment;
+use serde::Deserialize;
+use std::fmt::Display;
+use wasm_bindgen::prelude::*;
+
+// Bindings for making codes in the browser, built with wasm-pack or wasm-bindgen for wasm32-unknown-unknown
+// They go through the same encoder and SVG renderer as everything else, so codes come out exactly as they do natively
+
+// Everything that can be set when making a code from JS, like { ec: "H", scale: "10x", dark: "#1a2b3c" }
+// Anything left out gets the same default as the command line
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct Options {
+    ec: Option<String>,
+    // numeric, alphanumeric, byte or auto
+    mode: Option<String>,
+    version: Option<usize>,
+    mask: Option<usize>,
+    bump_ec: bool,
+    // Image size in pixels, or pixels per module like 10x
+    scale: Option<String>,
+    quiet_zone: Option<u32>,
+    dark: Option<String>,
+    light: Option<String>,
+    shape: Option<String>,
+    finder_ring: Option<String>,
+    finder_eye: Option<String>,
+    swiss_cross: bool,
+}
+
+fn js_error(error: impl Display) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+// A generated code, which JS can draw itself from the modules or turn into an SVG
+#[wasm_bindgen]
+pub struct Code {
+    code: QR,
+    options: RenderOptions,
+}
+
+#[wasm_bindgen]
+impl Code {
+    // Makes a code out of text, with the options as a plain object that can be left out
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &str, options: JsValue) -> Result<Code, JsError> {
+        Code::from_bytes(data.as_bytes(), options)
+    }
+
+    // Makes a code out of a Uint8Array, which doesn't have to be text
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8], options: JsValue) -> Result<Code, JsError> {
+        let options: Options = if options.is_undefined() || options.is_null() {
+            Options::default()
+        } else {
+            // Going through JSON keeps deny_unknown_fields working, so typos in option names are caught
+            let json = js_sys::JSON::stringify(&options).map_err(|_| js_error("options can't be read"))?;
+            serde_json::from_str(&String::from(json)).map_err(js_error)?
+        };
+
+        let segment = match options.mode.as_deref() {
+            None | Some("auto") => Segment::auto(data),
+            Some(mode) => Segment::with_mode(mode.parse().map_err(js_error)?, data).map_err(js_error)?,
+        };
+
+        let mut render_options = RenderOptions::default();
+
+        if let Some(scale) = &options.scale {
+            render_options.scale = scale.parse().map_err(js_error)?;
+        }
+
+        if let Some(shape) = &options.shape {
+            render_options.module_shape = shape.parse().map_err(js_error)?;
+        }
+
+        if let Some(shape) = &options.finder_ring {
+            render_options.finder_ring = shape.parse().map_err(js_error)?;
+        }
+
+        if let Some(shape) = &options.finder_eye {
+            render_options.finder_eye = shape.parse().map_err(js_error)?;
+        }
+
+        render_options.quiet_zone = options.quiet_zone.unwrap_or(render_options.quiet_zone);
+        render_options.swiss_cross = options.swiss_cross;
+
+        for (color, text) in [
+            (&mut render_options.dark, &options.dark),
+            (&mut render_options.light, &options.light),
+        ] {
+            if let Some(text) = text {
+                *color = render::parse_color(text).ok_or_else(|| js_error(format!("{} isn't a color", text)))?;
+            }
+        }
+
+        let ec_level = match &options.ec {
+            Some(level) => level.parse().map_err(js_error)?,
+            None => EcLevel::Q,
+        };
+
+        let code = render::fit(
+            vec![segment],
+            ec_level,
+            options.version,
+            options.mask,
+            options.bump_ec,
+            &render_options,
+        )
+        .map_err(js_error)?;
+
+        Ok(Code {
+            code,
+            options: render_options,
+        })
+    }
+
+    // Modules along each side, not counting the quiet zone
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.code.size
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> usize {
+        self.code.version
+    }
+
+    #[wasm_bindgen(getter, js_name = ecLevel)]
+    pub fn ec_level(&self) -> String {
+        format!("{:?}", self.code.ec_level)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn mask(&self) -> usize {
+        self.code.mask
+    }
+
+    // Every module row by row as a Uint8Array of size * size, 1 for dark and 0 for light
+    pub fn modules(&self) -> Vec<u8> {
+        (0..self.code.size)
+            .flat_map(|y| (0..self.code.size).map(move |x| (x, y)))
+            .map(|(x, y)| self.code.is_dark(x, y) as u8)
+            .collect()
+    }
+
+    // Whether the module at column x, row y is dark
+    #[wasm_bindgen(js_name = isDark)]
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.code.size && y < self.code.size && self.code.is_dark(x, y)
+    }
+
+    // The code as an SVG document, drawn with the options it was made with
+    pub fn svg(&self) -> String {
+        svg::svg(&self.code, &self.options)
+    }
+}
+
+// Shorthand for making a code out of text and turning it straight into an SVG
+#[wasm_bindgen(js_name = toSvg)]
+pub fn to_svg(data: &str, options: JsValue) -> Result<String, JsError> {
+    Ok(Code::new(data, options)?.svg())
+}
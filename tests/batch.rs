@@ -0,0 +1,111 @@
+#![cfg(feature = "std")]
+
+use qr::batch::{self, Outcome, Settings};
+use qr::qr::EcLevel;
+use qr::render::RenderOptions;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn settings() -> Settings {
+    Settings {
+        ec_level: EcLevel::M,
+        mode: None,
+        version: None,
+        mask: None,
+        bump_ec: false,
+        format: None,
+        options: RenderOptions::default(),
+    }
+}
+
+// A fresh directory for a test, and the output directory inside it
+fn directories(name: &str) -> (PathBuf, PathBuf) {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let directory = root.join("codes");
+    (root, directory)
+}
+
+// The good row is saved, the bad one fails and the ones leaving the output directory don't write anything
+fn check(outcomes: &[Outcome], root: &Path, directory: &Path, lines: [usize; 4]) {
+    assert_eq!(outcomes.len(), 4);
+    assert_eq!(outcomes.iter().map(|outcome| outcome.line).collect::<Vec<_>>(), lines);
+
+    assert_eq!(outcomes[0].error, None);
+    assert_eq!(outcomes[0].metadata.as_ref().unwrap().ec_level, EcLevel::M);
+    assert!(directory.join("good/hello.png").is_file());
+
+    assert!(outcomes[1].error.as_ref().unwrap().contains("Z"));
+    assert!(outcomes[1].metadata.is_none());
+    assert!(!directory.join("bad.png").exists());
+
+    for outcome in outcomes[2..].iter() {
+        assert!(outcome.error.as_ref().unwrap().contains("inside the output directory"));
+        assert!(outcome.metadata.is_none());
+    }
+
+    assert!(!root.join("escaped.png").exists());
+}
+
+#[test]
+fn reports_csv_rows() {
+    let (root, directory) = directories("batch-csv");
+    let escaped = root.join("escaped.png");
+
+    let csv = format!(
+        "data,output,ec\nhello,good/hello.png,\nbroken,bad.png,Z\nsneaky,../escaped.png,\nsneaky,{},\n",
+        escaped.display()
+    );
+    let rows = batch::read_csv(csv.as_bytes()).unwrap();
+    let outcomes = batch::run(&rows, &settings(), &directory);
+
+    check(&outcomes, &root, &directory, [2, 3, 4, 5]);
+    assert_eq!(outcomes[3].output.as_deref(), Some(escaped.to_str().unwrap()));
+}
+
+#[test]
+fn reports_json_lines_rows() {
+    let (root, directory) = directories("batch-jsonl");
+
+    let jsonl = concat!(
+        "{\"data\": \"hello\", \"output\": \"good/hello.png\"}\n",
+        "{\"data\": \"broken\", \"output\": \"bad.png\", \"ec\": \"Z\"}\n",
+        "\n",
+        "{\"data\": \"sneaky\", \"output\": \"../escaped.png\"}\n",
+        "{\"data\": \"sneaky\", \"output\": \"good/../../escaped.png\"}\n",
+    );
+    let rows = batch::read_json_lines(jsonl);
+    let outcomes = batch::run(&rows, &settings(), &directory);
+
+    check(&outcomes, &root, &directory, [1, 2, 4, 5]);
+}
+
+#[test]
+fn fails_rows_sharing_an_output() {
+    let (_, directory) = directories("batch-shared");
+    let rows = batch::read_json_lines(concat!(
+        "{\"data\": \"one\", \"output\": \"same.png\"}\n",
+        "{\"data\": \"two\", \"output\": \"./same.png\"}\n",
+        "{\"data\": \"three\", \"output\": \"other.png\"}\n",
+    ));
+    let outcomes = batch::run(&rows, &settings(), &directory);
+
+    assert_eq!(outcomes[0].error.as_deref(), Some("same.png is the output of lines 1, 2"));
+    assert_eq!(outcomes[1].error.as_deref(), Some("./same.png is the output of lines 1, 2"));
+
+    assert_eq!(outcomes[2].error, None);
+    assert!(!directory.join("same.png").exists());
+    assert!(directory.join("other.png").is_file());
+}
+
+#[test]
+fn keeps_spaces_in_csv_data() {
+    let csv = batch::read_csv(" data , output\n 12 ,padded.png\n".as_bytes()).unwrap();
+    let jsonl = batch::read_json_lines("{\"data\": \" 12 \", \"output\": \"padded.png\"}\n");
+
+    let row = csv[0].1.as_ref().unwrap();
+    assert_eq!(row.data, " 12 ");
+    assert_eq!(row.output, "padded.png");
+    assert_eq!(row.data, jsonl[0].1.as_ref().unwrap().data);
+}
@@ -0,0 +1,127 @@
+/* Exercises the C interface the way firmware would, built and run by tests/ffi.rs */
+
+#include "qr.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define CHECK(condition)                                                        \
+    do {                                                                        \
+        if (!(condition)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+            return 1;                                                           \
+        }                                                                       \
+    } while (0)
+
+static int makes_symbols(void) {
+    const char *text = "HELLO WORLD";
+    QrOptions options = qr_options_default();
+    options.ec_level = QR_EC_LEVEL_M;
+    options.mask = 3;
+
+    QrSymbol *symbol = NULL;
+    CHECK(qr_symbol_new((const uint8_t *)text, strlen(text), &options, &symbol) == QR_STATUS_OK);
+    CHECK(symbol != NULL);
+
+    size_t size = qr_symbol_size(symbol);
+    CHECK(size == 21);
+    CHECK(qr_symbol_version(symbol) == 1);
+    CHECK(qr_symbol_mask(symbol) == 3);
+    CHECK(qr_symbol_ec_level(symbol) == QR_EC_LEVEL_M);
+
+    uint8_t *modules = malloc(size * size);
+    CHECK(qr_symbol_modules(symbol, modules, size * size - 1) == QR_STATUS_BUFFER_TOO_SMALL);
+    CHECK(qr_symbol_modules(symbol, modules, size * size) == QR_STATUS_OK);
+
+    /* The top left finder pattern: a dark ring, a light ring and a dark centre */
+    CHECK(modules[0] == 1 && modules[6] == 1 && modules[size + 1] == 0 && modules[3 * size + 3] == 1);
+
+    for (size_t y = 0; y < size; y++) {
+        for (size_t x = 0; x < size; x++) {
+            CHECK(modules[y * size + x] == qr_symbol_is_dark(symbol, x, y));
+        }
+    }
+
+    CHECK(!qr_symbol_is_dark(symbol, size, 0));
+
+    free(modules);
+    qr_symbol_free(symbol);
+    return 0;
+}
+
+static int renders_into_buffers(void) {
+    const uint8_t data[] = {0x00, 0x9f, 0x92, 0x96};
+    QrSymbol *symbol = NULL;
+    CHECK(qr_symbol_new(data, sizeof data, NULL, &symbol) == QR_STATUS_OK);
+
+    size_t needed = 0;
+    CHECK(qr_symbol_render(symbol, QR_FORMAT_PNG, NULL, 0, &needed) == QR_STATUS_BUFFER_TOO_SMALL);
+    CHECK(needed > 8);
+
+    uint8_t *png = malloc(needed);
+    size_t written = 0;
+    CHECK(qr_symbol_render(symbol, QR_FORMAT_PNG, png, needed, &written) == QR_STATUS_OK);
+    CHECK(written == needed);
+    CHECK(memcmp(png, "\x89PNG\r\n\x1a\n", 8) == 0);
+    free(png);
+
+    CHECK(qr_symbol_render(symbol, QR_FORMAT_SVG, NULL, 0, &needed) == QR_STATUS_BUFFER_TOO_SMALL);
+    char *svg = malloc(needed + 1);
+    CHECK(qr_symbol_render(symbol, QR_FORMAT_SVG, (uint8_t *)svg, needed, &written) == QR_STATUS_OK);
+    svg[written] = '\0';
+    CHECK(strncmp(svg, "<svg", 4) == 0);
+    free(svg);
+
+    qr_symbol_free(symbol);
+    return 0;
+}
+
+static int reports_errors(void) {
+    QrSymbol *symbol = NULL;
+    QrOptions options = qr_options_default();
+
+    options.mode = QR_MODE_NUMERIC;
+    CHECK(qr_symbol_new((const uint8_t *)"12a", 3, &options, &symbol) == QR_STATUS_INVALID_FIELD);
+
+    options = qr_options_default();
+    options.version = 1;
+    uint8_t long_data[100] = {0};
+    CHECK(qr_symbol_new(long_data, sizeof long_data, &options, &symbol) == QR_STATUS_TOO_LONG_FOR_VERSION);
+
+    uint8_t *huge = calloc(4000, 1);
+    CHECK(qr_symbol_new(huge, 4000, NULL, &symbol) == QR_STATUS_TOO_LONG);
+    free(huge);
+
+    CHECK(symbol == NULL);
+    CHECK(qr_symbol_new(NULL, 5, NULL, &symbol) == QR_STATUS_NULL_POINTER);
+    CHECK(strcmp(qr_status_message(QR_STATUS_TOO_LONG), "message is too long for any version") == 0);
+    CHECK(strcmp(qr_status_message(1000), "unknown status") == 0);
+
+    /* Numbers that aren't one of an enum's values are turned down rather than trusted */
+    options = qr_options_default();
+    options.ec_level = 4;
+    CHECK(qr_symbol_new((const uint8_t *)"HELLO", 5, &options, &symbol) == QR_STATUS_INVALID_FIELD);
+
+    options = qr_options_default();
+    options.mode = 0xffffffff;
+    CHECK(qr_symbol_new((const uint8_t *)"HELLO", 5, &options, &symbol) == QR_STATUS_INVALID_FIELD);
+    CHECK(symbol == NULL);
+
+    CHECK(qr_symbol_new((const uint8_t *)"HELLO", 5, NULL, &symbol) == QR_STATUS_OK);
+    size_t needed = 0;
+    CHECK(qr_symbol_render(symbol, 5, NULL, 0, &needed) == QR_STATUS_INVALID_FIELD);
+    qr_symbol_free(symbol);
+
+    qr_symbol_free(NULL);
+    return 0;
+}
+
+int main(void) {
+    if (makes_symbols() || renders_into_buffers() || reports_errors()) {
+        return 1;
+    }
+
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,42 @@
+#![cfg(all(unix, feature = "std"))]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+// Builds tests/ffi.c against the static library and include/qr.h, then runs it
+#[test]
+fn c_program_runs() {
+    // Test binaries live in <target>/<profile>/deps, and the libraries one level up
+    let exe = env::current_exe().unwrap();
+    let profile_dir = exe.parent().and_then(Path::parent).unwrap();
+    let target_dir = profile_dir.parent().unwrap();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    // The static library comes from the qr-ffi crate, which the tests of this one don't build on their own
+    let mut build = Command::new(env!("CARGO"));
+    build.args(["build", "--package", "qr-ffi", "--target-dir"]).arg(target_dir).current_dir(root);
+
+    if profile_dir.ends_with("release") {
+        build.arg("--release");
+    }
+
+    assert!(build.status().unwrap().success());
+
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let compiled = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&program)
+        .arg(root.join("tests/ffi.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(profile_dir.join("libqr.a"))
+        .args(["-lpthread", "-ldl", "-lm"])
+        .status()
+        .unwrap();
+    assert!(compiled.success());
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"ok\n");
+}
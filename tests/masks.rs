@@ -0,0 +1,105 @@
+use qr::error::Error;
+use qr::qr::{EcLevel, PenaltyRules, QR};
+
+// Penalties for masks 0-7 and the mask picked, from qrcodegen 1.8.0, which follows ISO/IEC 18004:2015
+const REFERENCE: [(&str, EcLevel, [usize; 8], usize); 12] = [
+    ("HELLO WORLD", EcLevel::L, [1037, 1270, 1286, 1092, 1176, 1191, 1106, 1064], 0),
+    ("HELLO WORLD", EcLevel::M, [1135, 1090, 1063, 1200, 1011, 1156, 1141, 1197], 4),
+    ("HELLO WORLD", EcLevel::Q, [1055, 1124, 1083, 1091, 1073, 1159, 1245, 1027], 7),
+    ("HELLO WORLD", EcLevel::H, [1386, 1354, 1247, 1225, 1426, 1424, 1496, 1305], 3),
+    ("https://github.com/fluxehub/qr", EcLevel::L, [1361, 1282, 1293, 1348, 1281, 1317, 1181, 1288], 6),
+    ("https://github.com/fluxehub/qr", EcLevel::M, [1310, 1423, 1392, 1390, 1414, 1435, 1386, 1349], 0),
+    ("https://github.com/fluxehub/qr", EcLevel::Q, [1321, 1487, 1488, 1268, 1366, 1591, 1372, 1341], 3),
+    ("https://github.com/fluxehub/qr", EcLevel::H, [1584, 1569, 1549, 1522, 1643, 1538, 1593, 1527], 3),
+    ("01234567", EcLevel::H, [1531, 1341, 1292, 1240, 1320, 1366, 1360, 1241], 3),
+    ("Hello, world!", EcLevel::Q, [1220, 1371, 1259, 1369, 1182, 1488, 1442, 1171], 7),
+    ("a", EcLevel::H, [1100, 1140, 1102, 1146, 1316, 1195, 1069, 1138], 6),
+    ("QR", EcLevel::M, [1167, 1148, 1000, 1203, 1068, 1068, 1041, 1238], 2),
+];
+
+// "HELLO WORLD" in byte mode at level M, as qrcodegen 1.8.0 draws it
+const HELLO_WORLD: [&str; 21] = [
+    "#######.##..#.#######",
+    "#.....#....#..#.....#",
+    "#.###.#..#.#..#.###.#",
+    "#.###.#.#..#..#.###.#",
+    "#.###.#.###.#.#.###.#",
+    "#.....#.#..#..#.....#",
+    "#######.#.#.#.#######",
+    "........#..##........",
+    "#...#.######.#####..#",
+    "...#....#.###....####",
+    "..######..##.##.#..#.",
+    "#####...##...#.......",
+    "#####.#.#.#.#.##..##.",
+    "........#.#.####.#.##",
+    "#######.###.#.#.##.#.",
+    "#.....#..#.###.##..##",
+    "#.###.#.##.#.##...##.",
+    "#.###.#..#..#...##.##",
+    "#.###.#..###...###...",
+    "#.....#....#.#.......",
+    "#######.#########.#.#",
+];
+
+fn generated(data: &str, ec_level: EcLevel, rules: PenaltyRules) -> QR {
+    let mut qr = QR::with_bytes(data.as_bytes(), ec_level).unwrap();
+    qr.set_penalty_rules(rules);
+    qr.generate();
+    qr
+}
+
+#[test]
+fn scores_masks_like_the_2015_edition() {
+    for &(data, ec_level, penalties, mask) in REFERENCE.iter() {
+        let qr = generated(data, ec_level, PenaltyRules::Iso2015);
+        assert_eq!(qr.mask_penalties(), penalties, "{:?} at {:?}", data, ec_level);
+        assert_eq!(qr.metadata(false).mask, mask, "{:?} at {:?}", data, ec_level);
+    }
+}
+
+#[test]
+fn matches_reference_symbol() {
+    let qr = generated("HELLO WORLD", EcLevel::M, PenaltyRules::default());
+
+    for (y, row) in HELLO_WORLD.iter().enumerate() {
+        for (x, module) in row.chars().enumerate() {
+            assert_eq!(qr.is_dark(x, y), module == '#', "module ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn scores_masks_like_the_2006_edition() {
+    // Patterns only count with 4 light modules inside the symbol, so the finders themselves mostly stop scoring
+    let qr = generated("HELLO WORLD", EcLevel::L, PenaltyRules::Iso2006);
+    assert_eq!(qr.mask_penalties(), [317, 550, 486, 292, 496, 471, 386, 344]);
+    assert_eq!(qr.metadata(false).mask, 3);
+
+    let qr = generated("HELLO WORLD", EcLevel::M, PenaltyRules::Iso2006);
+    assert_eq!(qr.mask_penalties(), [415, 370, 303, 480, 291, 396, 381, 477]);
+    assert_eq!(qr.metadata(false).mask, 4);
+}
+
+#[test]
+fn forced_masks_keep_their_penalties() {
+    let mut qr = QR::with_bytes(b"HELLO WORLD", EcLevel::Q).unwrap();
+    qr.generate_with_mask(2).unwrap();
+    assert_eq!(qr.metadata(false).mask, 2);
+    assert_eq!(qr.mask_penalties(), REFERENCE[2].2);
+}
+
+#[test]
+fn rejects_masks_past_7() {
+    let mut qr = QR::with_bytes(b"HELLO WORLD", EcLevel::Q).unwrap();
+    assert!(matches!(qr.generate_with_mask(8), Err(Error::InvalidField { field: "mask", .. })));
+    assert!(qr.generate_with_mask(usize::MAX).is_err());
+    assert!(qr.generate_with_mask(7).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "mask 8 isn't between 0 and 7")]
+fn has_no_pattern_for_masks_past_7() {
+    assert!(QR::mask_applies(7, 0, 0));
+    QR::mask_applies(8, 0, 0);
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "std")]
+
+use image::{imageops, GrayImage, Luma, Rgba, RgbaImage};
+use qr::decode;
+use qr::error::Error;
+use qr::qr::{EcLevel, QR};
+use qr::render::logo::Logo;
+use qr::render::rgb;
+use qr::render::{self, RenderOptions, Scale, MAX_IMAGE_SIZE};
+use qr::segment::Segment;
+
+const URL: &[u8] = b"https://example.com/a/page/with/a/logo";
+
+fn fit(options: &RenderOptions) -> Result<QR, Error> {
+    render::fit(vec![Segment::auto(b"HELLO")], EcLevel::M, None, None, false, options)
+}
+
+#[test]
+fn turns_down_images_too_big_to_draw() {
+    let too_large = Err(Error::ImageTooLarge { max: MAX_IMAGE_SIZE });
+
+    for scale in [Scale::PerModule(100_000_000), Scale::PerModule(u32::MAX), Scale::Fit(MAX_IMAGE_SIZE + 1)] {
+        let options = RenderOptions { scale, ..RenderOptions::default() };
+        assert_eq!(fit(&options).map(|_| ()), too_large);
+    }
+
+    let options = RenderOptions { quiet_zone: u32::MAX, ..RenderOptions::default() };
+    assert_eq!(fit(&options).map(|_| ()), too_large);
+
+    // Codes made some other way are still checked when they're drawn
+    let code = fit(&RenderOptions::default()).unwrap();
+    let options = RenderOptions { scale: Scale::PerModule(u32::MAX), ..RenderOptions::default() };
+    assert_eq!(render::render(&code, &options).map(|_| ()), too_large);
+    let written = render::write(&code, Vec::new(), render::Format::Png, &options);
+    assert!(matches!(written, Err(image::ImageError::Limits(_))));
+}
+
+#[test]
+fn draws_images_up_to_the_limit() {
+    let options = RenderOptions { scale: Scale::Fit(MAX_IMAGE_SIZE), ..RenderOptions::default() };
+    let code = fit(&options).unwrap();
+    assert_eq!(render::image_size(&code, &options), Ok(MAX_IMAGE_SIZE));
+}
+
+fn logo(size: f64) -> RenderOptions {
+    let picture = RgbaImage::from_pixel(40, 30, Rgba([0, 0, 0, 255]));
+    RenderOptions { logo: Some(Logo { image: Some(picture), size }), ..RenderOptions::default() }
+}
+
+#[test]
+fn reads_back_codes_under_a_logo() {
+    let options = logo(0.25);
+    let code = render::fit(vec![Segment::auto(URL)], EcLevel::H, None, None, false, &options).unwrap();
+    let image = imageops::grayscale(&render::render(&code, &options).unwrap());
+
+    // The logo is solid black, so the modules it hides only come back through error correction
+    let decoded = decode::read_image(&image).unwrap();
+    assert_eq!(decoded.data(), URL);
+    assert!(decoded.corrected > 0);
+}
+
+#[test]
+fn raises_the_ec_level_for_big_logos() {
+    let options = logo(0.3);
+    let fit = |bump_ec| render::fit(vec![Segment::auto(URL)], EcLevel::L, None, None, bump_ec, &options);
+
+    assert!(matches!(fit(false), Err(Error::LogoTooBig { .. })));
+
+    let code = fit(true).unwrap();
+    assert_ne!(code.ec_level, EcLevel::L);
+    let image = imageops::grayscale(&render::render(&code, &options).unwrap());
+    assert_eq!(decode::decode_image(&image).unwrap(), URL);
+
+    // Nothing can make up for a logo that covers most of the code
+    let options = logo(0.8);
+    let fit = render::fit(vec![Segment::auto(URL)], EcLevel::L, None, None, true, &options);
+    assert!(matches!(fit, Err(Error::LogoTooBig { .. })));
+}
+
+// A picture that's dark on the left and light on the right, with a light spot in the middle
+fn picture() -> GrayImage {
+    GrayImage::from_fn(64, 64, |x, y| {
+        let spot = (x as i32 - 32).pow(2) + (y as i32 - 32).pow(2) < 144;
+        Luma([if spot { 255 } else { (x * 4) as u8 }])
+    })
+}
+
+#[test]
+fn reads_back_halftones() {
+    let options = RenderOptions { halftone: Some(picture()), ..RenderOptions::default() };
+    let code = render::fit(vec![Segment::auto(b"HALFTONE")], EcLevel::M, None, None, false, &options).unwrap();
+    let image = imageops::grayscale(&render::render(&code, &options).unwrap());
+
+    // The error correction is worked out again after the padding is set, so nothing needs correcting
+    let decoded = decode::read_image(&image).unwrap();
+    assert_eq!(decoded.data(), b"HALFTONE");
+    assert_eq!(decoded.corrected, 0);
+}
+
+#[test]
+fn halftones_only_change_padding() {
+    let segments = vec![Segment::auto(b"HALFTONE")];
+    let options = RenderOptions { halftone: Some(picture()), ..RenderOptions::default() };
+    let halftone = render::fit(segments.clone(), EcLevel::M, None, None, false, &options).unwrap();
+    let mut plain = QR::with_segments(segments, EcLevel::M).unwrap();
+    plain.generate();
+
+    assert_eq!(halftone.version, plain.version);
+
+    // Data bits without their mask, so codes with different masks can be compared
+    let bit = |code: &QR, x, y| code.is_dark(x, y) != QR::mask_applies(code.mask, x, y);
+    let padding = plain.padding_codewords();
+    let mut changed = 0;
+
+    for y in 0..plain.size {
+        for x in 0..plain.size {
+            if let Some((codeword, _)) = plain.data_bit_at(x, y) {
+                if bit(&halftone, x, y) == bit(&plain, x, y) {
+                    continue;
+                }
+
+                assert!(padding.contains(&codeword), "codeword {} isn't padding", codeword);
+                changed += 1;
+            }
+        }
+    }
+
+    assert!(changed > 0);
+}
+
+#[test]
+fn reads_back_every_layer_of_rgb_codes() {
+    // The multi-byte characters make sure none of the splits land in the middle of one
+    let input = "Grüße aus Zürich — 三つのコードを一枚の画像に重ねて、三倍のデータを入れる";
+    let parts = rgb::split(input);
+    assert!(parts.iter().all(|part| !part.is_empty()));
+    assert_eq!(parts.concat(), input);
+
+    let codes = rgb::encode(parts.clone(), EcLevel::M).unwrap();
+    assert!(codes.iter().all(|code| code.version == codes[0].version));
+
+    let image = rgb::render(&codes, &RenderOptions::default()).unwrap();
+    let layers = rgb::decode(&image).unwrap();
+
+    for (layer, part) in layers.iter().zip(parts.iter()) {
+        assert_eq!(layer, part.as_bytes());
+    }
+
+    assert_eq!(layers.concat(), input.as_bytes());
+}
+
+#[test]
+fn turns_down_rgb_parts_too_long_for_any_version() {
+    let parts = ["short".to_string(), "x".repeat(3000), String::new()];
+    let too_long = Err(Error::TooLong { max: QR::max_length(EcLevel::M) });
+    assert_eq!(rgb::encode(parts, EcLevel::M).map(|_| ()), too_long);
+}
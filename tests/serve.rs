@@ -0,0 +1,100 @@
+#![cfg(feature = "std")]
+
+use qr::decode;
+use qr::serve::{self, Config, Server};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+
+#[test]
+fn makes_codes_that_read_back() {
+    let response = serve::handle("/qr?data=hello+w%C3%B6rld&ec=H&scale=4x", None, &Config::default());
+    assert_eq!(response.status, 200);
+    assert_eq!(response.content_type, "image/png");
+
+    let image = image::load_from_memory(&response.body).unwrap().to_luma();
+    assert_eq!(decode::decode_image(&image).unwrap(), "hello wörld".as_bytes());
+}
+
+#[test]
+fn answers_json_and_svg() {
+    let json = serve::handle("/qr?data=HELLO&format=json&version=3", None, &Config::default());
+    assert_eq!(json.content_type, "application/json");
+    assert!(String::from_utf8(json.body).unwrap().starts_with("{\"version\":3,"));
+
+    let svg = serve::handle("/qr?data=hi&format=svg", None, &Config::default());
+    assert_eq!(svg.content_type, "image/svg+xml");
+    assert!(svg.body.starts_with(b"<svg"));
+}
+
+#[test]
+fn caches_by_etag() {
+    let first = serve::handle("/qr?data=cached", None, &Config::default());
+    let etag = first.etag.unwrap();
+
+    let again = serve::handle("/qr?data=cached", Some(&etag), &Config::default());
+    assert_eq!(again.status, 304);
+    assert!(again.body.is_empty());
+
+    let other = serve::handle("/qr?data=other", Some(&etag), &Config::default());
+    assert_eq!(other.status, 200);
+
+    let any = serve::handle("/qr?data=other", Some("*"), &Config::default());
+    assert_eq!(any.status, 304);
+}
+
+#[test]
+fn checks_queries_before_matching_any_etag() {
+    let config = Config {
+        max_data: 4000,
+        ..Config::default()
+    };
+
+    let status = |url: &str| serve::handle(url, Some("\"stale\", *"), &config).status;
+
+    assert_eq!(status("/qr?data=hi&ec=Z"), 400);
+    assert_eq!(status("/qr?data=hi&mask=8"), 400);
+    assert_eq!(status("/qr?data=abcdefghij&version=1&ec=H"), 400);
+    assert_eq!(status(&format!("/qr?data={}", "a".repeat(3000))), 400);
+    assert_eq!(status(&format!("/qr?data={}", "a".repeat(4001))), 413);
+    assert_eq!(status("/qr?data=hi"), 304);
+}
+
+#[test]
+fn rejects_bad_requests() {
+    let config = Config {
+        max_data: 10,
+        max_size: 500,
+        ..Config::default()
+    };
+
+    let status = |url: &str| serve::handle(url, None, &config).status;
+
+    assert_eq!(status("/elsewhere?data=hi"), 404);
+    assert_eq!(status("/qr"), 400);
+    assert_eq!(status("/qr?data=hi&colour=red"), 400);
+    assert_eq!(status("/qr?data=hi&ec=Z"), 400);
+    assert_eq!(status("/qr?data=hi&mode=numeric"), 400);
+    assert_eq!(status("/qr?data=hi&scale=1000"), 400);
+    assert_eq!(status("/qr?data=0123456789a"), 413);
+    assert_eq!(status(&format!("/qr?data={}", "a".repeat(5000))), 414);
+}
+
+#[test]
+fn serves_over_http() {
+    let server = Server::bind("127.0.0.1:0", Config::default()).unwrap();
+    let address = server.address().unwrap();
+    thread::spawn(move || server.run());
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET /qr?data=over+http&format=svg HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("Content-Type: image/svg+xml"));
+    assert!(response.contains("Cache-Control: public, max-age=86400"));
+    assert!(response.contains("ETag: \""));
+    assert!(response.contains("<svg"));
+}
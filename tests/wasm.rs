@@ -0,0 +1,70 @@
+// Runs under Node with wasm-bindgen-test-runner, see .cargo/config.toml
+#![cfg(all(target_arch = "wasm32", feature = "std"))]
+
+use qr::qr::{EcLevel, QR};
+use qr::render::{self, svg, RenderOptions};
+use qr::segment::Segment;
+use qr::wasm::{self, Code};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn options(json: &str) -> JsValue {
+    js_sys::JSON::parse(json).unwrap()
+}
+
+// The same code made without going through the bindings
+fn native(data: &[u8], ec_level: EcLevel) -> QR {
+    render::fit(
+        vec![Segment::auto(data)],
+        ec_level,
+        None,
+        None,
+        false,
+        &RenderOptions::default(),
+    )
+    .unwrap()
+}
+
+#[wasm_bindgen_test]
+fn matches_the_native_encoder() {
+    let code = Code::new("HELLO WORLD", options(r#"{"ec": "H", "scale": "10x"}"#)).unwrap();
+
+    let expected = native(b"HELLO WORLD", EcLevel::H);
+    assert_eq!(
+        (code.size(), code.version(), code.mask()),
+        (expected.size, expected.version, expected.mask)
+    );
+    assert_eq!(code.ec_level(), "H");
+
+    let modules = code.modules();
+    assert_eq!(modules.len(), code.size() * code.size());
+
+    for y in 0..code.size() {
+        for x in 0..code.size() {
+            assert_eq!(modules[y * code.size() + x] == 1, expected.is_dark(x, y));
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn makes_svgs() {
+    let made = wasm::to_svg("hello", JsValue::UNDEFINED).unwrap();
+    assert_eq!(made, svg::svg(&native(b"hello", EcLevel::Q), &RenderOptions::default()));
+
+    let colored = wasm::to_svg("hello", options(r#"{"dark": "1a2b3c", "shape": "circle"}"#)).unwrap();
+    assert!(colored.contains("#1a2b3c"));
+}
+
+#[wasm_bindgen_test]
+fn takes_bytes() {
+    let code = Code::from_bytes(&[0, 159, 146, 150], options(r#"{"version": 2, "mask": 5}"#)).unwrap();
+    assert_eq!((code.version(), code.mask()), (2, 5));
+}
+
+#[wasm_bindgen_test]
+fn rejects_bad_options() {
+    assert!(Code::new("hi", options(r#"{"ec": "Z"}"#)).is_err());
+    assert!(Code::new("hi", options(r#"{"colour": "red"}"#)).is_err());
+    assert!(Code::new("hi", options(r#"{"mode": "numeric"}"#)).is_err());
+    assert!(Code::new(&"a".repeat(3000), JsValue::NULL).is_err());
+}